@@ -1,4 +1,6 @@
-use crate::entry::Quantity;
+use std::ops::Range;
+
+use crate::entry::{Address, Quantity};
 use crate::utils::{expr_assign_predicate, get_punctuated, panic_not_literal};
 use syn::{punctuated::Punctuated, token::Comma, DeriveInput, Expr, ExprAssign, Lit};
 
@@ -6,6 +8,8 @@ use syn::{punctuated::Punctuated, token::Comma, DeriveInput, Expr, ExprAssign, L
 pub struct Config {
     pub max_cnt_per_request: Quantity,
     pub allow_register_gaps: bool,
+    /// Register ranges which must not be read as gaps between the fields
+    pub forbidden: Vec<Range<u32>>,
 }
 
 impl Config {
@@ -15,22 +19,24 @@ impl Config {
         let name = &ast.ident.to_string();
 
         // Try to extract relevant fields from the attribute
-        let (max_cnt_per_request, allow_register_gaps) = match attr {
+        let (max_cnt_per_request, allow_register_gaps, forbidden) = match attr {
             Some(attr) => {
                 let punctuated = get_punctuated(attr, name);
 
                 let max_cnt_per_request = Self::get_max_cnt_per_request(&punctuated, name);
                 let allow_register_gaps = Self::get_allow_register_gaps(&punctuated, name);
+                let forbidden = Self::get_forbidden(&punctuated, name);
 
-                (max_cnt_per_request, allow_register_gaps)
+                (max_cnt_per_request, allow_register_gaps, forbidden)
             }
-            None => (None, None),
+            None => (None, None, None),
         };
 
         Self {
             // https://en.wikipedia.org/wiki/Modbus#Function_codes_4_(read_input_registers)_and_3_(read_holding_registers)
             max_cnt_per_request: max_cnt_per_request.unwrap_or(123),
             allow_register_gaps: allow_register_gaps.unwrap_or(false),
+            forbidden: forbidden.unwrap_or_default(),
         }
     }
 
@@ -69,4 +75,59 @@ impl Config {
             .next()
             .map(|lit_bool| lit_bool.value())
     }
+
+    fn get_forbidden(
+        punctuated: &Punctuated<ExprAssign, Comma>,
+        name: &str,
+    ) -> Option<Vec<Range<u32>>> {
+        punctuated
+            .iter()
+            .filter(expr_assign_predicate("forbidden", name))
+            .map(|expr_assign| match *expr_assign.right.clone() {
+                Expr::Lit(right) => match right.lit {
+                    Lit::Str(lit_str) => lit_str.value(),
+                    _ => panic_not_literal("forbidden", "string", name),
+                },
+                _ => panic_not_literal("forbidden", "", name),
+            })
+            .next()
+            .map(|value| {
+                Self::parse_ranges(&value).unwrap_or_else(|| panic!("In `modbus` attribute for `{name}`, the key `forbidden` could not be parsed to comma separated addresses or address ranges, e.g. \"100..120, 200\"."))
+            })
+    }
+
+    /// Parse comma separated addresses and address ranges, e.g. `"100..120, 130..=140, 200"`.
+    fn parse_ranges(value: &str) -> Option<Vec<Range<u32>>> {
+        let parse = |addr: &str| addr.trim().parse::<Address>().ok().map(u32::from);
+        value
+            .split(',')
+            .filter(|item| !item.trim().is_empty())
+            .map(|item| {
+                let range = if let Some((start, end)) = item.split_once("..=") {
+                    parse(start)?..parse(end)? + 1
+                } else if let Some((start, end)) = item.split_once("..") {
+                    parse(start)?..parse(end)?
+                } else {
+                    let addr = parse(item)?;
+                    addr..addr + 1
+                };
+                (range.start < range.end).then_some(range)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ranges() {
+        assert_eq!(
+            Config::parse_ranges("100..120, 130..=140,200"),
+            Some(vec![100..120, 130..141, 200..201])
+        );
+        assert_eq!(Config::parse_ranges("120..100"), None);
+        assert_eq!(Config::parse_ranges("a..b"), None);
+    }
 }
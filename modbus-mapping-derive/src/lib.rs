@@ -18,7 +18,13 @@ pub fn derive_input_register_map(input: TokenStream) -> TokenStream {
     let block_config = config::Config::new(&ast);
 
     let mapping = mapping::Mapping::new(&ast);
+    let words_block = update_from_words_block(&mapping);
     let block_mappings = mapping.split_into_block_mappings(&block_config);
+    let report_block = with_report_block(
+        &block_mappings,
+        "update_from_input_registers_with_report",
+        "read_input_registers",
+    );
 
     let mut blocks = Vec::new();
     for mapping in block_mappings {
@@ -61,6 +67,10 @@ pub fn derive_input_register_map(input: TokenStream) -> TokenStream {
                 #(#blocks)*
                 Ok(Ok(()))
             }
+
+            #words_block
+
+            #report_block
         }
 
     };
@@ -68,6 +78,142 @@ pub fn derive_input_register_map(input: TokenStream) -> TokenStream {
     tokens.into()
 }
 
+/// `update_from_*_registers_with_report` method reading `block_mappings` by `read_method` shared by `InputRegisterMap` and `HoldingRegisterMap`
+fn with_report_block(
+    block_mappings: &[mapping::Mapping],
+    method: &str,
+    read_method: &str,
+) -> proc_macro2::TokenStream {
+    let method = proc_macro2::Ident::new(method, proc_macro2::Span::call_site());
+    let read_method = proc_macro2::Ident::new(read_method, proc_macro2::Span::call_site());
+
+    let mut blocks = Vec::new();
+    for mapping in block_mappings {
+        let field_name = mapping.field_name_vec();
+        let field_ty = mapping.field_ty_vec();
+        let x = mapping.x_vec();
+        let addr = mapping.addr_vec();
+        let ty = mapping.ty_vec();
+        let cnt = mapping.cnt_vec();
+        let from_words = mapping.fn_from_words_vec();
+
+        let (start, end) = mapping.register_range();
+        let len = end - start;
+
+        let block = quote! {
+            // Read
+            let timestamp = std::time::SystemTime::now();
+            let result = client.#read_method(#start, #len).await?;
+            #(
+                // Decode, convert and scale
+                let #field_name: Result<#field_ty, modbus_mapping::core::FieldStatus> = match &result {
+                    Ok(words) => match words
+                        .get((#addr - #start) as usize..(#addr - #start + #cnt) as usize)
+                        .map(|words| -> Result<#ty, _> { modbus_mapping::codec::Decode::#from_words(words) })
+                    {
+                        #[allow(clippy::unnecessary_cast)]
+                        Some(Ok(value)) => Ok((value as #field_ty) * (#x as #field_ty)),
+                        _ => Err(modbus_mapping::core::FieldStatus::DecodeError),
+                    },
+                    Err(exc) => Err(modbus_mapping::core::FieldStatus::Exception(*exc)),
+                };
+                report.fields.push(modbus_mapping::core::FieldReport {
+                    name: stringify!(#field_name),
+                    status: #field_name.as_ref().err().copied().unwrap_or(modbus_mapping::core::FieldStatus::Ok),
+                    timestamp,
+                });
+                // Set
+                if let (modbus_mapping::core::UpdateMode::FieldByField, Ok(value)) = (mode, #field_name) {
+                    self.#field_name = value;
+                    report.updated = true;
+                }
+            )*
+        };
+        blocks.push(block);
+    }
+
+    let field_name = block_mappings
+        .iter()
+        .flat_map(|mapping| mapping.field_name_vec())
+        .collect::<Vec<_>>();
+
+    quote! {
+        async fn #method(
+            &mut self,
+            client: &mut dyn tokio_modbus::client::Reader,
+            mode: modbus_mapping::core::UpdateMode,
+        ) -> Result<modbus_mapping::core::ReadReport, tokio_modbus::Error> {
+            let mut report = modbus_mapping::core::ReadReport::default();
+            #(#blocks)*
+            // Set all fields once read in atomic mode
+            if mode == modbus_mapping::core::UpdateMode::Atomic && report.is_ok() {
+                #(
+                    if let Ok(value) = #field_name {
+                        self.#field_name = value;
+                        report.updated = true;
+                    }
+                )*
+            }
+            Ok(report)
+        }
+    }
+}
+
+/// `field_registers` and `update_from_words` methods shared by `InputRegisterMap` and `HoldingRegisterMap`
+fn update_from_words_block(mapping: &mapping::Mapping) -> proc_macro2::TokenStream {
+    let field_name = mapping.field_name_vec();
+    let field_ty = mapping.field_ty_vec();
+    let x = mapping.x_vec();
+    let addr = mapping.addr_vec();
+    let ty = mapping.ty_vec();
+    let cnt = mapping.cnt_vec();
+    let from_words = mapping.fn_from_words_vec();
+    let unit = mapping.unit_vec();
+
+    quote! {
+        fn field_registers() -> Vec<(tokio_modbus::Address, tokio_modbus::Quantity)> {
+            vec![#((#addr, #cnt)),*]
+        }
+
+        fn field_units() -> Vec<(&'static str, &'static str)> {
+            vec![#((stringify!(#field_name), #unit)),*]
+        }
+
+        fn update_from_words(&mut self, words: &modbus_mapping::plan::RegisterWords) -> Result<(), tokio_modbus::Exception> {
+            #(
+                // Decode
+                let #field_name: #ty = modbus_mapping::codec::Decode::#from_words(
+                    words.get(#addr, #cnt).ok_or(tokio_modbus::Exception::IllegalDataAddress)?
+                ).unwrap();
+                // Convert and scale
+                #[allow(clippy::unnecessary_cast)]
+                let #field_name: #field_ty = (#field_name as #field_ty) * (#x as #field_ty);
+            )*
+            // Set all fields once decoded
+            #(
+                self.#field_name = #field_name;
+            )*
+            Ok(())
+        }
+
+        fn update_from_available_words(&mut self, words: &modbus_mapping::plan::RegisterWords) -> Vec<&'static str> {
+            let mut unavailable = Vec::new();
+            #(
+                match words.get(#addr, #cnt) {
+                    Some(field_words) => {
+                        let #field_name: #ty = modbus_mapping::codec::Decode::#from_words(field_words).unwrap();
+                        #[allow(clippy::unnecessary_cast)]
+                        let #field_name: #field_ty = (#field_name as #field_ty) * (#x as #field_ty);
+                        self.#field_name = #field_name;
+                    }
+                    None => unavailable.push(stringify!(#field_name)),
+                }
+            )*
+            unavailable
+        }
+    }
+}
+
 /// Derive macro to implement `modbus_mapping::core::HoldingRegisterMap`
 #[proc_macro_derive(HoldingRegisterMap, attributes(modbus))]
 pub fn derive_holding_register_map(input: TokenStream) -> TokenStream {
@@ -78,7 +224,13 @@ pub fn derive_holding_register_map(input: TokenStream) -> TokenStream {
     let block_config = config::Config::new(&ast);
 
     let mapping = mapping::Mapping::new(&ast);
+    let words_block = update_from_words_block(&mapping);
     let block_mappings = mapping.clone().split_into_block_mappings(&block_config);
+    let report_block = with_report_block(
+        &block_mappings,
+        "update_from_holding_registers_with_report",
+        "read_holding_registers",
+    );
 
     let mut read_blocks = Vec::new();
     for mapping in block_mappings {
@@ -172,6 +324,10 @@ pub fn derive_holding_register_map(input: TokenStream) -> TokenStream {
                 #(#write_blocks)*;
                 Ok(Ok(()))
             }
+
+            #words_block
+
+            #report_block
         }
 
         impl #name {
@@ -231,6 +387,7 @@ pub fn derive_input_register_model(input: TokenStream) -> TokenStream {
     let addr = mapping.addr_vec();
     let ty = mapping.ty_vec();
     let to_words = mapping.fn_to_words_vec();
+    let field_name_str = field_name.iter().map(|name| name.to_string());
 
     let tokens = quote! {
         impl modbus_mapping::simulator::InputRegisterModel for #name {
@@ -259,6 +416,20 @@ pub fn derive_input_register_model(input: TokenStream) -> TokenStream {
                 )*
                 Ok(())
             }
+
+            fn set_field(&mut self, name: &str, value: f64) -> bool {
+                match name {
+                    #(
+                        #field_name_str => {
+                            #[allow(clippy::unnecessary_cast)]
+                            let value = value as #field_ty;
+                            self.#field_name = value;
+                            true
+                        }
+                    )*
+                    _ => false,
+                }
+            }
         }
     };
 
@@ -283,6 +454,7 @@ pub fn derive_holding_register_model(input: TokenStream) -> TokenStream {
     let ty = mapping.ty_vec();
     let to_words = mapping.fn_to_words_vec();
     let from_words = mapping.fn_from_words_vec();
+    let field_name_str = field_name.iter().map(|name| name.to_string());
 
     let tokens = quote! {
         impl modbus_mapping::simulator::HoldingRegisterModel for #name {
@@ -329,6 +501,29 @@ pub fn derive_holding_register_model(input: TokenStream) -> TokenStream {
 
                 Ok(())
             }
+
+            fn field_values(
+                &self,
+                registers: &modbus_mapping::simulator::Registers,
+                addrs: std::ops::Range<tokio_modbus::Address>,
+            ) -> Result<Vec<(&'static str, f64)>, tokio_modbus::Exception> {
+                let mut values = Vec::new();
+                #(
+                    if #addr < addrs.end && addrs.start < #addr + #cnt {
+                        // Read
+                        let words = registers.read(#addr, #cnt)?;
+                        // Decode
+                        let value: #ty = modbus_mapping::codec::Decode::#from_words(&words)
+                            .map_err(|_| tokio_modbus::Exception::ServerDeviceFailure)?;
+                        // Convert and scale
+                        #[allow(clippy::unnecessary_cast)]
+                        let value = ((value as #field_ty) * (#x as #field_ty)) as f64;
+                        values.push((#field_name_str, value));
+                    }
+                )*
+
+                Ok(values)
+            }
         }
 
     };
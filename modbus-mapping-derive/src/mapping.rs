@@ -80,6 +80,10 @@ impl Mapping {
         self.0.iter().map(|x| x.x).collect::<Vec<_>>()
     }
 
+    pub fn unit_vec(&self) -> Vec<String> {
+        self.0.iter().map(|x| x.unit.clone()).collect::<Vec<_>>()
+    }
+
     pub fn register_range(&self) -> (Address, Address) {
         if self.0.is_empty() {
             (0, 0)
@@ -105,9 +109,14 @@ impl Mapping {
                 let last = entries.last().unwrap();
                 let max_cond =
                     entry.addr + entry.ty.word_size() - first.addr <= config.max_cnt_per_request;
-                let gap_cond = last.addr + last.ty.word_size() == entry.addr;
+                let gap = (last.addr + last.ty.word_size()) as u32..entry.addr as u32;
+                let gap_cond = gap.is_empty();
+                let forbidden_cond = config
+                    .forbidden
+                    .iter()
+                    .any(|range| range.start < gap.end && gap.start < range.end);
 
-                if max_cond && (gap_cond || config.allow_register_gaps) {
+                if max_cond && (gap_cond || (config.allow_register_gaps && !forbidden_cond)) {
                     entries.push(entry)
                 } else {
                     let mapping = Mapping(entries.clone());
@@ -139,7 +148,7 @@ mod tests {
                 .map(|addr| Entry {
                     field_name: format!("field_{}", addr),
                     field_ty: format!("ty_{}", addr),
-                    addr: addr,
+                    addr,
                     ty: DataType::F32,
                     ord: WordOrder::BigEndian,
                     x: 1.0,
@@ -152,6 +161,7 @@ mod tests {
             let config = Config {
                 max_cnt_per_request: 8,
                 allow_register_gaps,
+                forbidden: vec![],
             };
             let result = mapping
                 .clone()
@@ -161,5 +171,18 @@ mod tests {
                 .collect::<Vec<_>>();
             assert_eq!(result, expected)
         }
+
+        // The gap between the first two fields is not readable
+        let config = Config {
+            max_cnt_per_request: 8,
+            allow_register_gaps: true,
+            forbidden: vec![3..4, 20..30],
+        };
+        let result = mapping
+            .split_into_block_mappings(&config)
+            .iter()
+            .map(|m| m.0.len())
+            .collect::<Vec<_>>();
+        assert_eq!(result, vec![1, 3])
     }
 }
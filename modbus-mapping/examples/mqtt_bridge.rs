@@ -0,0 +1,67 @@
+/// Bridge between TCP Modbus device and MQTT broker, e.g. local mosquitto or rumqttd
+use modbus_mapping::{
+    client::ManagedClient,
+    core::InputRegisterMap,
+    derive::{HoldingRegisterMap, InputRegisterMap},
+    mqtt::MqttBridge,
+};
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet};
+use serde::{Deserialize, Serialize};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+use tokio_modbus::{client::Context, slave::Slave};
+
+#[derive(Debug, Clone, Default, Serialize, InputRegisterMap)]
+pub struct BatteryInputRegisters {
+    #[modbus(addr = 0, ty = "u32", ord = "be", x = 1.0, unit = "W")]
+    pub power: f32,
+    #[modbus(addr = 2, ty = "u32", ord = "be", x = 100.0, unit = "Wh")]
+    pub state_of_energy: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, HoldingRegisterMap)]
+pub struct BatteryHoldingRegisters {
+    #[modbus(addr = 0, ty = "i32", ord = "be", x = 0.01, unit = "W")]
+    pub setpoint: f32,
+}
+
+#[tokio::main]
+async fn main() {
+    let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8502);
+    let mut client = Context::from(ManagedClient::tcp(socket_addr, Slave(0)));
+
+    let (mqtt, mut event_loop) =
+        AsyncClient::new(MqttOptions::new("modbus-mapping", "127.0.0.1", 1883), 10);
+    let bridge = MqttBridge::new(mqtt, "battery");
+    bridge.subscribe_setpoints("holding").await.unwrap();
+
+    let mut hr = BatteryHoldingRegisters::default();
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                match BatteryInputRegisters::from_input_registers(&mut client).await {
+                    Ok(Ok(ir)) => bridge.publish_input_registers("input", &ir).await.unwrap(),
+                    Ok(Err(exc)) => eprintln!("{exc}"),
+                    Err(err) => eprintln!("{err}"),
+                }
+            }
+            event = event_loop.poll() => match event {
+                Ok(Event::Incoming(Packet::Publish(publish))) => {
+                    match bridge.apply_setpoint("holding", &mut hr, &publish, &mut client).await {
+                        Ok(Ok(_)) => bridge.publish_holding_registers("holding", &hr).await.unwrap(),
+                        Ok(Err(exc)) => eprintln!("{exc}"),
+                        Err(err) => eprintln!("{err}"),
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    eprintln!("{err}");
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
+    }
+}
@@ -1,7 +1,9 @@
 /// RTU Modbus client
+use std::time::Duration;
+
 use modbus_mapping::{
-    core::InputRegisterMap,
     derive::{HoldingRegisterMap, InputRegisterMap},
+    poller::Poller,
 };
 use tokio_modbus::{client::rtu::attach_slave, slave::Slave};
 
@@ -26,18 +28,17 @@ pub struct BatteryHoldingRegisters {
 #[tokio::main]
 async fn main() {
     let path = "/tmp/ttys002";
-    let baud_rate = 0;
+    let baud_rate = 9600;
     let builder = tokio_serial::new(path, baud_rate);
     let serial_stream = tokio_serial::SerialStream::open(&builder).unwrap();
     let slave = Slave(0);
-    let mut client = attach_slave(serial_stream, slave);
+    let client = attach_slave(serial_stream, slave);
 
-    loop {
-        let ir = BatteryInputRegisters::from_input_registers(&mut client)
-            .await
-            .unwrap()
-            .unwrap();
-        std::thread::sleep(std::time::Duration::from_millis(200));
-        println!("{:?}", ir);
+    let poller = Poller::new(client, Duration::from_millis(200))
+        .spawn_input_registers::<BatteryInputRegisters>();
+    let mut snapshots = poller.subscribe();
+    while snapshots.changed().await.is_ok() {
+        let snapshot = snapshots.borrow_and_update().clone();
+        println!("{:?} {:?}", snapshot.quality, snapshot.value);
     }
 }
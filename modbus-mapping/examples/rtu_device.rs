@@ -1,10 +1,7 @@
 /// Battery RTU Modbus simulator
-use futures::future;
 use modbus_mapping::derive::{HoldingRegisterModel, InputRegisterModel};
-use modbus_mapping::simulator::{
-    run_rtu_simulator, DataStore, Device, InputRegisterModel, Simulator,
-};
-use tokio_modbus::{Exception, Request, Response};
+use modbus_mapping::simulator::{run_rtu_simulator, ModelDevice, Simulator};
+use std::time::Duration;
 
 #[derive(Debug, Clone, Default, InputRegisterModel)]
 pub struct BatteryInputRegisters {
@@ -24,39 +21,24 @@ pub struct BatteryHoldingRegisters {
     pub setpoint: f32,
 }
 
-#[derive(Debug, Clone, Default)]
-struct Battery {
-    ir: BatteryInputRegisters,
-    hr: BatteryHoldingRegisters,
-    data_store: DataStore<BatteryInputRegisters, BatteryHoldingRegisters>,
-}
-
-impl Device for Battery {
-    type InputRegisters = BatteryInputRegisters;
-    type HoldingRegisters = BatteryHoldingRegisters;
-
-    fn service_call(&mut self, req: Request) -> future::Ready<Result<Response, Exception>> {
-        self.data_store.service_call(&mut self.hr, req)
-    }
-
-    fn update_state(&mut self) {
-        eprintln!("Updating state");
-        self.ir.power += 1.0;
-
-        let _ = self
-            .ir
-            .update_registers(&mut self.data_store.input_registers);
-    }
-}
-
 #[tokio::main]
 async fn main() {
-    let device = Battery::default();
+    let device = ModelDevice::new(
+        BatteryInputRegisters::default(),
+        BatteryHoldingRegisters::default(),
+        |ir: &mut BatteryInputRegisters, hr: &mut BatteryHoldingRegisters, elapsed: Duration| {
+            eprintln!("Updating state");
+            ir.power = hr.setpoint;
+            ir.state_of_energy += ir.power * elapsed.as_secs_f32() / 3600.0;
+        },
+    );
     let simulator = Simulator::new(device);
-    let state_update_period: std::time::Duration = std::time::Duration::from_millis(200);
+    let state_update_period = Duration::from_millis(200);
 
     let path = "/tmp/ttys001";
-    let baud_rate = 0;
+    let baud_rate = 9600;
 
-    run_rtu_simulator(path, baud_rate, simulator, state_update_period).await;
+    run_rtu_simulator(path, baud_rate, simulator, state_update_period)
+        .await
+        .unwrap();
 }
@@ -1,10 +1,12 @@
 /// TCP Modbus client
 use modbus_mapping::{
+    client::ManagedClient,
     core::InputRegisterMap,
     derive::{HoldingRegisterMap, InputRegisterMap},
+    identification::DeviceIdentification,
 };
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use tokio_modbus::{client::tcp::connect_slave, slave::Slave};
+use tokio_modbus::{client::Context, slave::Slave};
 
 #[derive(Debug, Clone, Default, InputRegisterMap)]
 pub struct BatteryInputRegisters {
@@ -29,14 +31,22 @@ async fn main() {
     let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8502);
     let slave = Slave(0);
 
-    let mut client = connect_slave(socket_addr, slave).await.unwrap();
+    let managed = ManagedClient::tcp(socket_addr, slave);
+    let state = managed.subscribe();
+    let mut client = Context::from(managed);
+
+    match DeviceIdentification::from_client(&mut client).await {
+        Ok(Ok(device_identification)) => println!("{:?}", device_identification),
+        Ok(Err(exc)) => eprintln!("Device identification not supported: {exc}"),
+        Err(err) => eprintln!("{err}"),
+    }
 
     loop {
-        let ir = BatteryInputRegisters::from_input_registers(&mut client)
-            .await
-            .unwrap()
-            .unwrap();
-        std::thread::sleep(std::time::Duration::from_millis(200));
-        println!("{:?}", ir);
+        match BatteryInputRegisters::from_input_registers(&mut client).await {
+            Ok(Ok(ir)) => println!("{:?}", ir),
+            Ok(Err(exc)) => eprintln!("{exc}"),
+            Err(err) => eprintln!("{err} ({:?})", *state.borrow()),
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
     }
 }
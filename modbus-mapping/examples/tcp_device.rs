@@ -1,6 +1,7 @@
 /// Battery TCP Modbus simulator
 use futures::future;
 use modbus_mapping::derive::{HoldingRegisterModel, InputRegisterModel};
+use modbus_mapping::identification::DeviceIdentification;
 use modbus_mapping::simulator::{
     run_tcp_simulator, DataStore, Device, InputRegisterModel, Simulator,
 };
@@ -8,6 +9,7 @@ use rand_chacha::rand_core::SeedableRng;
 use rand_chacha::ChaCha8Rng;
 use rand_distr::{Distribution, Normal};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
 use tokio_modbus::{Exception, Request, Response};
 
 #[derive(Debug, Clone, Default, InputRegisterModel)]
@@ -38,10 +40,14 @@ struct Battery {
 }
 impl Default for Battery {
     fn default() -> Self {
+        let mut data_store = DataStore::default();
+        data_store.device_identification =
+            DeviceIdentification::new("ACME", "BAT-1", "1.0").unwrap();
+
         Self {
             ir: BatteryInputRegisters::default(),
             hr: BatteryHoldingRegisters::default(),
-            data_store: DataStore::default(),
+            data_store,
             seed_rng: ChaCha8Rng::seed_from_u64(0),
             grid_freq_distr: Normal::new(50.0, 0.1).unwrap(),
         }
@@ -56,9 +62,10 @@ impl Device for Battery {
         self.data_store.service_call(&mut self.hr, req)
     }
 
-    fn update_state(&mut self) {
+    fn update_state(&mut self, elapsed: Duration) {
         eprintln!("Updating state");
         self.ir.power += 1.0;
+        self.ir.state_of_energy += self.ir.power * elapsed.as_secs_f32() / 3600.0;
         self.ir.grid_frequency = self.grid_freq_distr.sample(&mut self.seed_rng);
 
         // Sync with data_store
@@ -73,7 +80,9 @@ async fn main() {
     let device = Battery::default();
     let simulator = Simulator::new(device);
     let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8502);
-    let state_update_period: std::time::Duration = std::time::Duration::from_millis(200);
+    let state_update_period = Duration::from_millis(200);
 
-    run_tcp_simulator(socket_addr, simulator, state_update_period).await;
+    run_tcp_simulator(socket_addr, simulator, state_update_period)
+        .await
+        .unwrap();
 }
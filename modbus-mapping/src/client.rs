@@ -0,0 +1,9 @@
+mod bus;
+mod managed;
+mod rtu_over_tcp;
+mod udp;
+
+pub use bus::{silent_interval, Bus, BusClient, BusHandle};
+pub use managed::{ConnectionState, ManagedClient};
+pub use rtu_over_tcp::connect_rtu_over_tcp_slave;
+pub use udp::UdpClient;
@@ -0,0 +1,336 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io,
+    ops::Bound,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use tokio::sync::{mpsc, oneshot};
+use tokio_modbus::{
+    client::{Client, Context},
+    slave::{Slave, SlaveContext},
+    Request, Response,
+};
+
+/// Minimum silent interval between RTU frames for baud rates over 19200 bit/s.
+const MIN_SILENT_INTERVAL: Duration = Duration::from_micros(1750);
+
+/// Silent interval of 3.5 characters (of 11 bits) between RTU frames at `baud_rate`,
+/// fixed to 1.75 ms for baud rates over 19200 bit/s.
+pub fn silent_interval(baud_rate: u32) -> Duration {
+    if baud_rate == 0 || baud_rate > 19200 {
+        MIN_SILENT_INTERVAL
+    } else {
+        Duration::from_secs_f64(3.5 * 11.0 / baud_rate as f64)
+    }
+}
+
+/// Request queued to the bus.
+struct Pending {
+    queue: usize,
+    priority: u8,
+    slave: Slave,
+    request: Request<'static>,
+    reply: oneshot::Sender<tokio_modbus::Result<Response>>,
+}
+
+/// Message of [BusClient] to the bus task.
+enum Message {
+    Request(Pending),
+    /// The client of the queue was dropped.
+    Dropped(usize),
+}
+
+#[derive(Default)]
+/// Priority and waiting requests of each [BusClient] by its queue.
+struct Queues {
+    queues: BTreeMap<usize, (u8, VecDeque<Pending>)>,
+    /// The last served queue.
+    last: usize,
+}
+
+impl Queues {
+    fn receive(&mut self, message: Message) {
+        match message {
+            Message::Request(pending) => {
+                let queue = self.queues.entry(pending.queue).or_default();
+                queue.0 = pending.priority;
+                queue.1.push_back(pending);
+            }
+            Message::Dropped(queue) => {
+                self.queues.remove(&queue);
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.queues.values().all(|(_, queue)| queue.is_empty())
+    }
+
+    /// Pop request of the first waiting queue of the highest priority after the last served one.
+    fn pop(&mut self) -> Option<Pending> {
+        let after_last = (Bound::Excluded(self.last), Bound::Unbounded);
+        let mut selected: Option<(usize, u8)> = None;
+        for (&index, (priority, queue)) in self
+            .queues
+            .range(after_last)
+            .chain(self.queues.range(..=self.last))
+        {
+            if !queue.is_empty() && selected.is_none_or(|(_, selected)| *priority > selected) {
+                selected = Some((index, *priority));
+            }
+        }
+        let (index, _) = selected?;
+        self.last = index;
+        self.queues.get_mut(&index)?.1.pop_front()
+    }
+}
+
+/// Scheduler of requests of several slaves sharing one client, e.g. an RS-485 serial port.
+///
+/// The bus runs in a background task serving one request at a time.
+/// Waiting requests of higher priority are served first,
+/// requests of the same priority are served round-robin across the [BusClient]s.
+/// The silent interval is kept between the exchanges.
+///
+/// The requests are not timed out by the bus, wrap the client in [super::ManagedClient] if needed.
+pub struct Bus<C> {
+    client: C,
+    silent_interval: Duration,
+}
+
+impl<C: Client + 'static> Bus<C> {
+    /// Create bus serving the requests by `client`.
+    pub fn new(client: C) -> Self {
+        Self {
+            client,
+            silent_interval: MIN_SILENT_INTERVAL,
+        }
+    }
+
+    /// Set the silent interval between the exchanges, see [silent_interval].
+    pub fn with_silent_interval(mut self, silent_interval: Duration) -> Self {
+        self.silent_interval = silent_interval;
+        self
+    }
+
+    /// Run the bus in a background task until all its clients are dropped.
+    pub fn spawn(self) -> BusHandle {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(self.run(receiver));
+
+        BusHandle {
+            sender,
+            next_queue: 0,
+        }
+    }
+
+    async fn run(mut self, mut receiver: mpsc::UnboundedReceiver<Message>) {
+        let mut queues = Queues::default();
+        loop {
+            while let Ok(message) = receiver.try_recv() {
+                queues.receive(message);
+            }
+            if queues.is_empty() {
+                match receiver.recv().await {
+                    Some(message) => queues.receive(message),
+                    None => break,
+                }
+            }
+            let Some(pending) = queues.pop() else {
+                continue;
+            };
+
+            // The caller is not waiting anymore, e.g. cancelled by a timeout
+            if pending.reply.is_closed() {
+                continue;
+            }
+            self.client.set_slave(pending.slave);
+            let result = self.client.call(pending.request).await;
+            let _ = pending.reply.send(result);
+            tokio::time::sleep(self.silent_interval).await;
+        }
+    }
+}
+
+impl<C> std::fmt::Debug for Bus<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Bus")
+            .field("silent_interval", &self.silent_interval)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+/// Handle of the running [Bus] creating its clients.
+pub struct BusHandle {
+    sender: mpsc::UnboundedSender<Message>,
+    next_queue: usize,
+}
+
+impl BusHandle {
+    /// Create client of `slave` with the lowest priority.
+    pub fn client(&mut self, slave: Slave) -> BusClient {
+        self.client_with_priority(slave, 0)
+    }
+
+    /// Create client of `slave` whose requests are served before the waiting requests of lower `priority`.
+    pub fn client_with_priority(&mut self, slave: Slave, priority: u8) -> BusClient {
+        let queue = self.next_queue;
+        self.next_queue += 1;
+
+        BusClient {
+            sender: self.sender.clone(),
+            queue,
+            priority,
+            slave,
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Client of one device on [Bus].
+///
+/// Convert it into [Context] to use it with [`crate::core::InputRegisterMap`] and [`crate::core::HoldingRegisterMap`].
+pub struct BusClient {
+    sender: mpsc::UnboundedSender<Message>,
+    queue: usize,
+    priority: u8,
+    slave: Slave,
+}
+
+impl SlaveContext for BusClient {
+    fn set_slave(&mut self, slave: Slave) {
+        self.slave = slave;
+    }
+}
+
+#[async_trait]
+impl Client for BusClient {
+    async fn call(&mut self, request: Request<'_>) -> tokio_modbus::Result<Response> {
+        if let Request::Disconnect = request {
+            // The bus is shared by other clients, so it is not disconnected
+            return Err(io::Error::from(io::ErrorKind::NotConnected).into());
+        }
+        let (reply, response) = oneshot::channel();
+        let pending = Pending {
+            queue: self.queue,
+            priority: self.priority,
+            slave: self.slave,
+            request: request.into_owned(),
+            reply,
+        };
+        self.sender
+            .send(Message::Request(pending))
+            .map_err(|_| bus_stopped())?;
+        response.await.map_err(|_| bus_stopped())?
+    }
+}
+
+impl Drop for BusClient {
+    fn drop(&mut self) {
+        let _ = self.sender.send(Message::Dropped(self.queue));
+    }
+}
+
+fn bus_stopped() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "Bus stopped")
+}
+
+impl From<BusClient> for Context {
+    fn from(client: BusClient) -> Self {
+        Context::from(Box::new(client) as Box<dyn Client>)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio_modbus::client::Reader;
+
+    /// Client recording the served slaves.
+    #[derive(Debug)]
+    struct Served {
+        slave: Slave,
+        slaves: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl SlaveContext for Served {
+        fn set_slave(&mut self, slave: Slave) {
+            self.slave = slave;
+        }
+    }
+
+    #[async_trait]
+    impl Client for Served {
+        async fn call(&mut self, _request: Request<'_>) -> tokio_modbus::Result<Response> {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            self.slaves.lock().unwrap().push(self.slave.0);
+            Ok(Ok(Response::ReadInputRegisters(vec![self.slave.0 as u16])))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_bus() {
+        let served = Served {
+            slave: Slave(0),
+            slaves: Arc::default(),
+        };
+        let slaves = served.slaves.clone();
+        let mut bus = Bus::new(served).spawn();
+        let mut a = Context::from(bus.client(Slave(1)));
+        let mut b = Context::from(bus.client(Slave(2)));
+        let mut c = Context::from(bus.client_with_priority(Slave(3), 1));
+        drop(bus);
+
+        let start = tokio::time::Instant::now();
+        let (a, b, c) = tokio::join!(
+            async {
+                let first = a.read_input_registers(0, 1).await.unwrap();
+                (first, a.read_input_registers(0, 1).await.unwrap())
+            },
+            async {
+                let first = b.read_input_registers(0, 1).await.unwrap();
+                (first, b.read_input_registers(0, 1).await.unwrap())
+            },
+            c.read_input_registers(0, 1),
+        );
+        assert_eq!(a, (Ok(vec![1]), Ok(vec![1])));
+        assert_eq!(b, (Ok(vec![2]), Ok(vec![2])));
+        assert_eq!(c.unwrap(), Ok(vec![3]));
+
+        // The priority first, then round-robin
+        assert_eq!(*slaves.lock().unwrap(), vec![3, 1, 2, 1, 2]);
+        assert!(start.elapsed() >= Duration::from_millis(50) + MIN_SILENT_INTERVAL * 4);
+    }
+
+    #[test]
+    fn test_queues() {
+        let mut queues = Queues::default();
+        let request = |queue, priority| {
+            let (reply, _) = oneshot::channel();
+            Message::Request(Pending {
+                queue,
+                priority,
+                slave: Slave(queue as u8),
+                request: Request::ReadInputRegisters(0, 1),
+                reply,
+            })
+        };
+        for message in [request(0, 0), request(0, 0), request(1, 0), request(2, 0)] {
+            queues.receive(message);
+        }
+        assert_eq!(queues.pop().unwrap().queue, 1);
+        assert_eq!(queues.pop().unwrap().queue, 2);
+        assert_eq!(queues.pop().unwrap().queue, 0);
+
+        // Queue of the dropped client is removed with its waiting requests
+        queues.receive(Message::Dropped(0));
+        queues.receive(Message::Dropped(2));
+        assert_eq!(queues.queues.len(), 1);
+        assert!(queues.is_empty());
+        assert!(queues.pop().is_none());
+    }
+}
@@ -0,0 +1,306 @@
+use std::{fmt::Debug, future::Future, io, net::SocketAddr, pin::Pin, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use tokio::{
+    sync::watch,
+    time::{sleep_until, timeout, timeout_at, Instant},
+};
+use tokio_modbus::{
+    client::{tcp, Client, Context},
+    slave::{Slave, SlaveContext},
+    Request, Response,
+};
+
+/// Default timeout of the connection attempts and requests.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+/// Default delay before the first reconnection attempt.
+const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+/// Default maximum delay between the reconnection attempts.
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+type ConnectFuture = Pin<Box<dyn Future<Output = io::Result<Box<dyn Client>>> + Send>>;
+type Connect = Arc<dyn Fn() -> ConnectFuture + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Connection state of [ManagedClient].
+pub enum ConnectionState {
+    /// Not connected yet or disconnected on request; the next request connects.
+    Disconnected,
+    Connected,
+    /// The connection or its requests failed `attempts` times in a row; the next request connects again at `retry_at`.
+    BackingOff {
+        attempts: u32,
+        retry_at: Instant,
+    },
+}
+
+/// Client connecting on demand, reconnecting with exponential backoff and applying request timeouts.
+///
+/// Convert it into [Context] to use it with [`crate::core::InputRegisterMap`] and [`crate::core::HoldingRegisterMap`].
+///
+/// The connection is dropped after any transport error or timeout and reestablished after the backoff,
+/// which grows with the failures in a row until a request succeeds.
+/// A request issued while backing off waits for the reconnection if it is due within the timeout,
+/// otherwise it fails immediately with [io::ErrorKind::NotConnected] error.
+/// Failed requests are not retried.
+pub struct ManagedClient {
+    connect: Connect,
+    client: Option<Box<dyn Client>>,
+    slave: Option<Slave>,
+    /// Failed connection attempts and requests in a row.
+    attempts: u32,
+    timeout: Duration,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    state: watch::Sender<ConnectionState>,
+}
+
+impl ManagedClient {
+    /// Create client connecting by `connect`, e.g. `|| tokio_modbus::client::rtu::connect(...)`.
+    pub fn new<F, Fut, C>(connect: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = io::Result<C>> + Send + 'static,
+        C: Into<Box<dyn Client>>,
+    {
+        let connect: Connect = Arc::new(move || {
+            let connecting = connect();
+            Box::pin(async move { Ok(connecting.await?.into()) })
+        });
+
+        Self {
+            connect,
+            client: None,
+            slave: None,
+            attempts: 0,
+            timeout: DEFAULT_TIMEOUT,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            state: watch::Sender::new(ConnectionState::Disconnected),
+        }
+    }
+
+    /// Create client connecting to `slave` of Modbus TCP server at `socket_addr`.
+    pub fn tcp(socket_addr: SocketAddr, slave: Slave) -> Self {
+        let mut client = Self::new(move || tcp::connect_slave(socket_addr, slave));
+        client.slave = Some(slave);
+        client
+    }
+
+    /// Set timeout of the connection attempts and requests.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the delay before the first reconnection attempt, doubled after each failed attempt up to `max`.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Current connection state.
+    pub fn state(&self) -> ConnectionState {
+        *self.state.borrow()
+    }
+
+    /// Receiver notified about the connection state changes.
+    pub fn subscribe(&self) -> watch::Receiver<ConnectionState> {
+        self.state.subscribe()
+    }
+
+    fn set_state(&self, state: ConnectionState) {
+        self.state.send_if_modified(|current| {
+            let modified = *current != state;
+            *current = state;
+            modified
+        });
+    }
+
+    /// Drop the connection and back off for the delay doubled by each failure in a row.
+    fn back_off(&mut self) {
+        self.client = None;
+        self.attempts = self.attempts.saturating_add(1);
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(self.attempts - 1))
+            .min(self.max_backoff);
+        self.set_state(ConnectionState::BackingOff {
+            attempts: self.attempts,
+            retry_at: Instant::now() + backoff,
+        });
+    }
+
+    /// Return the connected client, connecting first if needed.
+    ///
+    /// Waiting for the end of the backoff and connecting are bounded by the timeout together.
+    async fn connected(&mut self) -> io::Result<&mut Box<dyn Client>> {
+        if self.client.is_none() {
+            let deadline = Instant::now() + self.timeout;
+            if let ConnectionState::BackingOff { retry_at, .. } = self.state() {
+                if retry_at >= deadline {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotConnected,
+                        "Backing off before reconnecting",
+                    ));
+                }
+                sleep_until(retry_at).await;
+            }
+
+            let connecting = timeout_at(deadline, (self.connect)());
+            match connecting.await.unwrap_or_else(|_| Err(timed_out())) {
+                Ok(mut client) => {
+                    if let Some(slave) = self.slave {
+                        client.set_slave(slave);
+                    }
+                    self.client = Some(client);
+                    self.set_state(ConnectionState::Connected);
+                }
+                Err(err) => {
+                    self.back_off();
+                    return Err(err);
+                }
+            }
+        }
+
+        self.client
+            .as_mut()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))
+    }
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "Timed out")
+}
+
+impl Debug for ManagedClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ManagedClient")
+            .field("state", &self.state())
+            .field("slave", &self.slave)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SlaveContext for ManagedClient {
+    fn set_slave(&mut self, slave: Slave) {
+        self.slave = Some(slave);
+        if let Some(client) = self.client.as_mut() {
+            client.set_slave(slave);
+        }
+    }
+}
+
+#[async_trait]
+impl Client for ManagedClient {
+    async fn call(&mut self, request: Request<'_>) -> tokio_modbus::Result<Response> {
+        if let Request::Disconnect = request {
+            let result = match self.client.take() {
+                Some(mut client) => client.call(request).await,
+                None => Err(io::Error::from(io::ErrorKind::NotConnected).into()),
+            };
+            self.set_state(ConnectionState::Disconnected);
+            return result;
+        }
+        let request_timeout = self.timeout;
+        let client = self.connected().await?;
+        let result = match timeout(request_timeout, client.call(request)).await {
+            Ok(Ok(result)) => {
+                self.attempts = 0;
+                return Ok(result);
+            }
+            Ok(Err(err)) => Err(err),
+            Err(_elapsed) => Err(timed_out().into()),
+        };
+
+        // The connection may be out of sync, e.g. a late response may arrive
+        self.back_off();
+        result
+    }
+}
+
+impl From<ManagedClient> for Context {
+    fn from(client: ManagedClient) -> Self {
+        Context::from(Box::new(client) as Box<dyn Client>)
+    }
+}
+
+#[cfg(all(test, feature = "simulator"))]
+mod tests {
+    use super::*;
+    use crate::simulator::testing::{local_context, replay_simulator};
+    use crate::simulator::FaultySimulation;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio_modbus::client::Reader;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_managed_client() {
+        let device = replay_simulator([(
+            1,
+            Request::ReadInputRegisters(0, 1),
+            Response::ReadInputRegisters(vec![42]),
+        )]);
+        let simulation = FaultySimulation::new(device)
+            .with_address_exception(1..=1, tokio_modbus::Exception::IllegalDataAddress);
+        let slow_simulation = simulation
+            .clone()
+            .with_latency(Duration::from_secs(5), Duration::ZERO);
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let client = ManagedClient::new({
+            let attempts = attempts.clone();
+            move || {
+                let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+                let simulation = simulation.clone();
+                let slow_simulation = slow_simulation.clone();
+                async move {
+                    match attempt {
+                        0 => Err(io::Error::from(io::ErrorKind::ConnectionRefused)),
+                        1 => Ok(local_context(slow_simulation, 1)),
+                        _ => Ok(local_context(simulation, 1)),
+                    }
+                }
+            }
+        })
+        .with_timeout(Duration::from_secs(1))
+        .with_backoff(Duration::from_millis(600), DEFAULT_MAX_BACKOFF);
+        let state = client.subscribe();
+        let mut client = Context::from(client);
+
+        // Connection refused, then backing off
+        assert!(client.read_input_registers(0, 1).await.is_err());
+        assert!(matches!(
+            *state.borrow(),
+            ConnectionState::BackingOff { attempts: 1, .. }
+        ));
+
+        // Reconnected after the backoff within the timeout, but the request times out
+        let err = client.read_input_registers(0, 1).await.unwrap_err();
+        assert!(
+            matches!(err, tokio_modbus::Error::Transport(err) if err.kind() == io::ErrorKind::TimedOut)
+        );
+        assert!(matches!(
+            *state.borrow(),
+            ConnectionState::BackingOff { attempts: 2, .. }
+        ));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+        // The doubled backoff exceeds the timeout, so the request fails without waiting
+        let err = client.read_input_registers(0, 1).await.unwrap_err();
+        assert!(
+            matches!(err, tokio_modbus::Error::Transport(err) if err.kind() == io::ErrorKind::NotConnected)
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+        // Reconnected after the doubled backoff; exceptions keep the connection
+        tokio::time::advance(Duration::from_millis(1200)).await;
+        assert_eq!(
+            client.read_input_registers(0, 1).await.unwrap(),
+            Ok(vec![42])
+        );
+        assert!(client.read_input_registers(1, 1).await.unwrap().is_err());
+        assert_eq!(*state.borrow(), ConnectionState::Connected);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+}
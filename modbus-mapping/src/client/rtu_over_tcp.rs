@@ -0,0 +1,67 @@
+use std::{io, net::SocketAddr};
+
+use tokio::net::TcpStream;
+use tokio_modbus::{
+    client::{rtu, Context},
+    slave::Slave,
+};
+
+/// Connect to `slave` behind a serial to TCP converter at `socket_addr`
+/// sending raw RTU frames over the TCP connection (RTU over TCP).
+///
+/// The requests are not timed out, wrap the client in [super::ManagedClient] if needed.
+pub async fn connect_rtu_over_tcp_slave(
+    socket_addr: SocketAddr,
+    slave: Slave,
+) -> io::Result<Context> {
+    Ok(rtu::attach_slave(
+        TcpStream::connect(socket_addr).await?,
+        slave,
+    ))
+}
+
+#[cfg(all(test, feature = "simulator"))]
+mod tests {
+    use super::*;
+    use crate::simulator::testing::replay_simulator;
+    use crate::simulator::TcpSimulator;
+    use tokio_modbus::{
+        client::{Reader, Writer},
+        Request, Response,
+    };
+
+    #[tokio::test]
+    async fn test_rtu_over_tcp_client() {
+        let device = replay_simulator([(
+            1,
+            Request::ReadHoldingRegisters(0, 2),
+            Response::ReadHoldingRegisters(vec![4, 2]),
+        )]);
+        let simulator = TcpSimulator::new("127.0.0.1:0".parse().unwrap(), device.clone())
+            .with_rtu_framing(true)
+            .spawn()
+            .await
+            .unwrap();
+
+        let mut client = connect_rtu_over_tcp_slave(simulator.local_addr().unwrap(), Slave(1))
+            .await
+            .unwrap();
+        assert_eq!(
+            client.read_holding_registers(0, 2).await.unwrap(),
+            Ok(vec![4, 2])
+        );
+        assert_eq!(
+            client.read_holding_registers(2, 1).await.unwrap(),
+            Err(tokio_modbus::Exception::IllegalDataAddress)
+        );
+        // The replayed record would overwrite the register written before the first state update
+        while !device.0.lock().await.is_finished() {
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        }
+        client.write_single_register(1, 7).await.unwrap().unwrap();
+        assert_eq!(
+            client.read_holding_registers(0, 2).await.unwrap(),
+            Ok(vec![4, 7])
+        );
+    }
+}
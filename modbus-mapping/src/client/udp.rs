@@ -0,0 +1,128 @@
+use std::{
+    io,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+use async_trait::async_trait;
+use tokio::net::UdpSocket;
+use tokio_modbus::{
+    client::{Client, Context},
+    slave::{Slave, SlaveContext},
+    Request, Response,
+};
+
+use crate::frame::{self, MBAP_HEADER_LEN};
+
+/// Maximum size of Modbus UDP datagram.
+const MAX_DATAGRAM_LEN: usize = 260;
+
+#[derive(Debug)]
+/// Modbus UDP client sending MBAP frames in datagrams.
+///
+/// Convert it into [Context] to use it with [`crate::core::InputRegisterMap`] and [`crate::core::HoldingRegisterMap`].
+/// Lost datagrams are not retried and the requests are not timed out, wrap the client in [super::ManagedClient] if needed.
+pub struct UdpClient {
+    socket: UdpSocket,
+    slave: Slave,
+    transaction_id: u16,
+}
+
+impl UdpClient {
+    /// Create client of `slave` of Modbus UDP server at `socket_addr`.
+    pub async fn connect(socket_addr: SocketAddr, slave: Slave) -> io::Result<Self> {
+        let local_addr: SocketAddr = match socket_addr {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local_addr).await?;
+        socket.connect(socket_addr).await?;
+
+        Ok(Self {
+            socket,
+            slave,
+            transaction_id: 0,
+        })
+    }
+}
+
+impl SlaveContext for UdpClient {
+    fn set_slave(&mut self, slave: Slave) {
+        self.slave = slave;
+    }
+}
+
+#[async_trait]
+impl Client for UdpClient {
+    async fn call(&mut self, request: Request<'_>) -> tokio_modbus::Result<Response> {
+        if let Request::Disconnect = request {
+            // There is no connection to close
+            return Err(io::Error::from(io::ErrorKind::NotConnected).into());
+        }
+        self.transaction_id = self.transaction_id.wrapping_add(1);
+        let frame = frame::encode_mbap_frame(
+            self.transaction_id,
+            self.slave.0,
+            &frame::encode_request(&request)?,
+        );
+        self.socket.send(&frame).await?;
+
+        let mut buf = [0; MAX_DATAGRAM_LEN];
+        loop {
+            let len = self.socket.recv(&mut buf).await?;
+            let datagram = &buf[..len];
+            // Skip malformed datagrams and late responses to the previous requests
+            let Ok((transaction_id, unit_id, pdu_len)) = frame::decode_mbap_header(datagram) else {
+                continue;
+            };
+            if transaction_id != self.transaction_id || unit_id != self.slave.0 {
+                continue;
+            }
+            let Some(pdu) = datagram.get(MBAP_HEADER_LEN..MBAP_HEADER_LEN + pdu_len) else {
+                continue;
+            };
+
+            return Ok(frame::decode_response(&request, pdu)?);
+        }
+    }
+}
+
+impl From<UdpClient> for Context {
+    fn from(client: UdpClient) -> Self {
+        Context::from(Box::new(client) as Box<dyn Client>)
+    }
+}
+
+#[cfg(all(test, feature = "simulator"))]
+mod tests {
+    use super::*;
+    use crate::simulator::testing::replay_simulator;
+    use crate::simulator::UdpSimulator;
+    use tokio_modbus::client::Reader;
+
+    #[tokio::test]
+    async fn test_udp_client() {
+        let device = replay_simulator([(
+            1,
+            Request::ReadInputRegisters(10, 2),
+            Response::ReadInputRegisters(vec![4, 2]),
+        )]);
+        let simulator = UdpSimulator::new("127.0.0.1:0".parse().unwrap(), device)
+            .spawn()
+            .await
+            .unwrap();
+
+        let mut client = Context::from(
+            UdpClient::connect(simulator.local_addr().unwrap(), Slave(1))
+                .await
+                .unwrap(),
+        );
+        assert_eq!(
+            client.read_input_registers(10, 2).await.unwrap(),
+            Ok(vec![4, 2])
+        );
+        assert_eq!(
+            client.read_input_registers(0, 1).await.unwrap(),
+            Err(tokio_modbus::Exception::IllegalDataAddress)
+        );
+    }
+}
@@ -5,9 +5,37 @@ pub use tokio_modbus::{Address, Quantity};
 /// 16-bit value stored in Modbus register.
 pub type Word = u16;
 
+/// Single bit value stored in Modbus coil or discrete input.
+pub type Coil = bool;
+
 #[derive(Debug)]
 pub struct WordsCountError {}
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Order of the words of multi-register values; the bytes within a word are always big-endian.
+pub enum WordOrder {
+    BigEndian,
+    LittleEndian,
+}
+
+impl WordOrder {
+    /// Decode a value from `words` in this order.
+    pub fn decode<T: Decode>(self, words: &[Word]) -> Result<T, WordsCountError> {
+        match self {
+            Self::BigEndian => T::from_be_words(words),
+            Self::LittleEndian => T::from_le_words(words),
+        }
+    }
+
+    /// Encode `value` into words in this order.
+    pub fn encode<T: Encode>(self, value: T) -> Vec<Word> {
+        match self {
+            Self::BigEndian => value.to_be_words(),
+            Self::LittleEndian => value.to_le_words(),
+        }
+    }
+}
+
 /// Decode a value from Big or Little Endian-ordered `Word`s.
 pub trait Decode: Sized {
     fn from_be_words(words: &[Word]) -> Result<Self, WordsCountError>;
@@ -26,8 +54,9 @@ macro_rules! impl_decode {
                 let array = bytes.try_into().or(Err(WordsCountError {}))?;
                 Ok(<$num_type>::from_be_bytes(array))
             }
-            fn from_le_words(_words: &[Word]) -> Result<Self, WordsCountError> {
-                todo!()
+            fn from_le_words(words: &[Word]) -> Result<Self, WordsCountError> {
+                let words = words.iter().rev().copied().collect::<Vec<_>>();
+                Self::from_be_words(&words)
             }
         }
     };
@@ -61,7 +90,9 @@ macro_rules! impl_encode {
                     .collect()
             }
             fn to_le_words(self) -> Vec<Word> {
-                todo!()
+                let mut words = self.to_be_words();
+                words.reverse();
+                words
             }
         }
     };
@@ -1,5 +1,77 @@
+use std::time::SystemTime;
+
 use async_trait::async_trait;
-use tokio_modbus::client::{Reader, Writer};
+use tokio_modbus::{
+    client::{Reader, Writer},
+    Address, Exception, Quantity,
+};
+
+use crate::plan::RegisterWords;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// How the fields are updated by the reads with [ReadReport].
+pub enum UpdateMode {
+    /// Update the fields only if all of them were read and decoded.
+    #[default]
+    Atomic,
+    /// Update each field read and decoded.
+    FieldByField,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Read status of a field.
+pub enum FieldStatus {
+    Ok,
+    /// The read request of the field block failed with the exception.
+    Exception(Exception),
+    /// The response did not contain the field words.
+    DecodeError,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Read status of a field with the time of its read request.
+pub struct FieldReport {
+    pub name: &'static str,
+    pub status: FieldStatus,
+    pub timestamp: SystemTime,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Per-field result of the read.
+pub struct ReadReport {
+    pub fields: Vec<FieldReport>,
+    /// Whether any field was updated.
+    pub updated: bool,
+}
+
+impl ReadReport {
+    /// Whether all fields were read and decoded.
+    pub fn is_ok(&self) -> bool {
+        self.fields
+            .iter()
+            .all(|field| field.status == FieldStatus::Ok)
+    }
+
+    /// Report of the whole type `T` read at once as a single field named by its type name.
+    fn whole<T>(result: Result<(), Exception>, timestamp: SystemTime) -> Self {
+        Self {
+            fields: vec![FieldReport {
+                name: std::any::type_name::<T>(),
+                status: result.map_or_else(FieldStatus::Exception, |()| FieldStatus::Ok),
+                timestamp,
+            }],
+            updated: result.is_ok(),
+        }
+    }
+
+    /// Status of the field `name`.
+    pub fn status(&self, name: &str) -> Option<FieldStatus> {
+        self.fields
+            .iter()
+            .find(|field| field.name == name)
+            .map(|field| field.status)
+    }
+}
 
 #[async_trait]
 /// Define mapping between Modbus input registers and the Self type
@@ -19,6 +91,54 @@ where
 
         Ok(Ok(new))
     }
+
+    /// Read all blocks and report the status of each field instead of stopping at the first exception;
+    /// the fields are updated according to `mode`.
+    /// Transport errors stop the read, the fields of the blocks read before are updated in [UpdateMode::FieldByField] mode.
+    ///
+    /// The default implementation updates by [Self::update_from_input_registers] regardless of `mode`
+    /// and reports the whole type as a single field named by its type name.
+    async fn update_from_input_registers_with_report(
+        &mut self,
+        client: &mut dyn Reader,
+        _mode: UpdateMode,
+    ) -> Result<ReadReport, tokio_modbus::Error> {
+        let timestamp = SystemTime::now();
+        let result = self.update_from_input_registers(client).await?;
+        Ok(ReadReport::whole::<Self>(result, timestamp))
+    }
+
+    /// Registers `(addr, cnt)` of the mapped fields to plan reads merged with other types by [`crate::plan::ReadPlan`].
+    fn field_registers() -> Vec<(Address, Quantity)> {
+        Vec::new()
+    }
+
+    /// Names and units of the mapped fields, e.g. to publish the values with units.
+    fn field_units() -> Vec<(&'static str, &'static str)> {
+        Vec::new()
+    }
+
+    /// Update the fields from `words` read by [`crate::plan::ReadPlan`];
+    /// fails with [Exception::IllegalDataAddress] without any update if some field registers were not read.
+    ///
+    /// The default implementation fails with [Exception::IllegalFunction], it is implemented by the derive macro.
+    fn update_from_words(&mut self, _words: &RegisterWords) -> Result<(), Exception> {
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Update the fields read in `words`, e.g. by adaptive [`crate::plan::ReadPlan`],
+    /// and return the names of the unavailable fields which were not updated.
+    ///
+    /// The default implementation updates all fields by [Self::update_from_words] or reports all [Self::field_units] unavailable.
+    fn update_from_available_words(&mut self, words: &RegisterWords) -> Vec<&'static str> {
+        match self.update_from_words(words) {
+            Ok(()) => Vec::new(),
+            Err(_) => Self::field_units()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect(),
+        }
+    }
 }
 
 #[async_trait]
@@ -40,5 +160,53 @@ where
         Ok(Ok(new))
     }
 
+    /// Read all blocks and report the status of each field instead of stopping at the first exception;
+    /// the fields are updated according to `mode`.
+    /// Transport errors stop the read, the fields of the blocks read before are updated in [UpdateMode::FieldByField] mode.
+    ///
+    /// The default implementation updates by [Self::update_from_holding_registers] regardless of `mode`
+    /// and reports the whole type as a single field named by its type name.
+    async fn update_from_holding_registers_with_report(
+        &mut self,
+        client: &mut dyn Reader,
+        _mode: UpdateMode,
+    ) -> Result<ReadReport, tokio_modbus::Error> {
+        let timestamp = SystemTime::now();
+        let result = self.update_from_holding_registers(client).await?;
+        Ok(ReadReport::whole::<Self>(result, timestamp))
+    }
+
+    /// Registers `(addr, cnt)` of the mapped fields to plan reads merged with other types by [`crate::plan::ReadPlan`].
+    fn field_registers() -> Vec<(Address, Quantity)> {
+        Vec::new()
+    }
+
+    /// Names and units of the mapped fields, e.g. to publish the values with units.
+    fn field_units() -> Vec<(&'static str, &'static str)> {
+        Vec::new()
+    }
+
+    /// Update the fields from `words` read by [`crate::plan::ReadPlan`];
+    /// fails with [Exception::IllegalDataAddress] without any update if some field registers were not read.
+    ///
+    /// The default implementation fails with [Exception::IllegalFunction], it is implemented by the derive macro.
+    fn update_from_words(&mut self, _words: &RegisterWords) -> Result<(), Exception> {
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Update the fields read in `words`, e.g. by adaptive [`crate::plan::ReadPlan`],
+    /// and return the names of the unavailable fields which were not updated.
+    ///
+    /// The default implementation updates all fields by [Self::update_from_words] or reports all [Self::field_units] unavailable.
+    fn update_from_available_words(&mut self, words: &RegisterWords) -> Vec<&'static str> {
+        match self.update_from_words(words) {
+            Ok(()) => Vec::new(),
+            Err(_) => Self::field_units()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect(),
+        }
+    }
+
     async fn write_to_registers(&self, client: &mut dyn Writer) -> tokio_modbus::Result<()>;
 }
@@ -0,0 +1,408 @@
+//! Encoding of Modbus PDUs for the recorded exchanges and of MBAP frames for the UDP transport.
+use std::{borrow::Cow, io};
+
+use crate::codec::{Coil, Word};
+use bytes::Bytes;
+use tokio_modbus::{Exception, Request, Response};
+
+/// Length of MBAP header including the unit id.
+pub(crate) const MBAP_HEADER_LEN: usize = 7;
+/// Maximum quantity of coils or discrete inputs read by a request.
+const MAX_READ_COILS: u16 = 2000;
+/// Maximum quantity of coils written by a request.
+const MAX_WRITE_COILS: u16 = 1968;
+/// Maximum quantity of registers read by a request.
+const MAX_READ_REGISTERS: u16 = 125;
+/// Maximum quantity of registers written by a request.
+const MAX_WRITE_REGISTERS: u16 = 123;
+/// Maximum quantity of registers written by Read/Write Multiple Registers request.
+const MAX_READ_WRITE_REGISTERS: u16 = 121;
+
+pub(crate) fn exception_code(exception: Exception) -> u8 {
+    match exception {
+        Exception::IllegalFunction => 0x01,
+        Exception::IllegalDataAddress => 0x02,
+        Exception::IllegalDataValue => 0x03,
+        Exception::ServerDeviceFailure => 0x04,
+        Exception::Acknowledge => 0x05,
+        Exception::ServerDeviceBusy => 0x06,
+        Exception::MemoryParityError => 0x08,
+        Exception::GatewayPathUnavailable => 0x0A,
+        Exception::GatewayTargetDevice => 0x0B,
+    }
+}
+
+pub(crate) fn exception_from_code(code: u8) -> Option<Exception> {
+    match code {
+        0x01 => Some(Exception::IllegalFunction),
+        0x02 => Some(Exception::IllegalDataAddress),
+        0x03 => Some(Exception::IllegalDataValue),
+        0x04 => Some(Exception::ServerDeviceFailure),
+        0x05 => Some(Exception::Acknowledge),
+        0x06 => Some(Exception::ServerDeviceBusy),
+        0x08 => Some(Exception::MemoryParityError),
+        0x0A => Some(Exception::GatewayPathUnavailable),
+        0x0B => Some(Exception::GatewayTargetDevice),
+        _ => None,
+    }
+}
+
+/// Encode the request PDU; fails if the written values do not fit in the PDU.
+///
+/// Panics on [Request::Disconnect] which has no PDU.
+pub(crate) fn encode_request(req: &Request) -> io::Result<Vec<u8>> {
+    let byte_count = |len: usize| {
+        u8::try_from(len).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Too many values to write in a request",
+            )
+        })
+    };
+    let mut pdu = vec![req.function_code().value()];
+    match req {
+        Request::ReadCoils(addr, cnt)
+        | Request::ReadDiscreteInputs(addr, cnt)
+        | Request::ReadHoldingRegisters(addr, cnt)
+        | Request::ReadInputRegisters(addr, cnt) => {
+            pdu.extend(addr.to_be_bytes());
+            pdu.extend(cnt.to_be_bytes());
+        }
+        Request::WriteSingleCoil(addr, coil) => {
+            pdu.extend(addr.to_be_bytes());
+            pdu.extend(if *coil { [0xFF, 0x00] } else { [0x00, 0x00] });
+        }
+        Request::WriteSingleRegister(addr, word) => {
+            pdu.extend(addr.to_be_bytes());
+            pdu.extend(word.to_be_bytes());
+        }
+        Request::WriteMultipleCoils(addr, coils) => {
+            let bytes = pack_coils(coils);
+            pdu.extend(addr.to_be_bytes());
+            pdu.extend((coils.len() as u16).to_be_bytes());
+            pdu.push(byte_count(bytes.len())?);
+            pdu.extend(bytes);
+        }
+        Request::WriteMultipleRegisters(addr, words) => {
+            pdu.extend(addr.to_be_bytes());
+            pdu.extend((words.len() as u16).to_be_bytes());
+            pdu.push(byte_count(words.len() * 2)?);
+            pdu.extend(words.iter().flat_map(|word| word.to_be_bytes()));
+        }
+        Request::MaskWriteRegister(addr, and_mask, or_mask) => {
+            pdu.extend(addr.to_be_bytes());
+            pdu.extend(and_mask.to_be_bytes());
+            pdu.extend(or_mask.to_be_bytes());
+        }
+        Request::ReadWriteMultipleRegisters(read_addr, cnt, write_addr, words) => {
+            pdu.extend(read_addr.to_be_bytes());
+            pdu.extend(cnt.to_be_bytes());
+            pdu.extend(write_addr.to_be_bytes());
+            pdu.extend((words.len() as u16).to_be_bytes());
+            pdu.push(byte_count(words.len() * 2)?);
+            pdu.extend(words.iter().flat_map(|word| word.to_be_bytes()));
+        }
+        Request::Custom(_, data) => pdu.extend_from_slice(data),
+        Request::Disconnect => unreachable!(),
+    }
+
+    Ok(pdu)
+}
+
+/// Decode the response (or exception) PDU to the `req` request.
+///
+/// Panics on [Request::Disconnect] which has no PDU.
+pub(crate) fn decode_response(
+    req: &Request,
+    pdu: &[u8],
+) -> io::Result<Result<Response, Exception>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Invalid response PDU");
+    let (&fc, data) = pdu.split_first().ok_or_else(invalid)?;
+    let request_fc = req.function_code().value();
+    if fc == request_fc | 0x80 {
+        let code = data.first().ok_or_else(invalid)?;
+        return exception_from_code(*code).map(Err).ok_or_else(invalid);
+    }
+    if fc != request_fc {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Response function code mismatch",
+        ));
+    }
+    let word = |i: usize| -> io::Result<Word> {
+        data.get(i..i + 2)
+            .map(|bytes| Word::from_be_bytes([bytes[0], bytes[1]]))
+            .ok_or_else(invalid)
+    };
+    let words = || -> io::Result<Vec<Word>> {
+        let cnt = *data.first().ok_or_else(invalid)? as usize / 2;
+        (0..cnt).map(|i| word(1 + 2 * i)).collect()
+    };
+    let coils = |cnt: u16| -> io::Result<Vec<Coil>> {
+        let bytes = data.get(1..).ok_or_else(invalid)?;
+        if bytes.len() * 8 < cnt as usize {
+            return Err(invalid());
+        }
+        Ok(unpack_coils(bytes, cnt))
+    };
+
+    let response = match req {
+        Request::ReadCoils(_, cnt) => Response::ReadCoils(coils(*cnt)?),
+        Request::ReadDiscreteInputs(_, cnt) => Response::ReadDiscreteInputs(coils(*cnt)?),
+        Request::ReadHoldingRegisters(..) => Response::ReadHoldingRegisters(words()?),
+        Request::ReadInputRegisters(..) => Response::ReadInputRegisters(words()?),
+        Request::ReadWriteMultipleRegisters(..) => Response::ReadWriteMultipleRegisters(words()?),
+        Request::WriteSingleCoil(..) => Response::WriteSingleCoil(word(0)?, word(2)? == 0xFF00),
+        Request::WriteSingleRegister(..) => Response::WriteSingleRegister(word(0)?, word(2)?),
+        Request::WriteMultipleCoils(..) => Response::WriteMultipleCoils(word(0)?, word(2)?),
+        Request::WriteMultipleRegisters(..) => Response::WriteMultipleRegisters(word(0)?, word(2)?),
+        Request::MaskWriteRegister(..) => Response::MaskWriteRegister(word(0)?, word(2)?, word(4)?),
+        Request::Custom(..) | Request::Disconnect => {
+            Response::Custom(fc, Bytes::copy_from_slice(data))
+        }
+    };
+
+    Ok(Ok(response))
+}
+
+/// Decode the request PDU. The function code and exception is returned on failure.
+pub(crate) fn decode_request(pdu: &[u8]) -> Result<Request<'static>, (u8, Exception)> {
+    let Some((&fc, data)) = pdu.split_first() else {
+        return Err((0x00, Exception::IllegalFunction));
+    };
+    let invalid = (fc, Exception::IllegalDataValue);
+    let word = |i: usize| -> Result<Word, (u8, Exception)> {
+        data.get(i..i + 2)
+            .map(|bytes| Word::from_be_bytes([bytes[0], bytes[1]]))
+            .ok_or(invalid)
+    };
+    // Quantity at `i` in 1..=max
+    let quantity = |i: usize, max: u16| -> Result<u16, (u8, Exception)> {
+        let cnt = word(i)?;
+        if (1..=max).contains(&cnt) {
+            Ok(cnt)
+        } else {
+            Err(invalid)
+        }
+    };
+    // Byte count at `i` of the `len` bytes following it
+    let byte_count = |i: usize, len: usize| -> Result<(), (u8, Exception)> {
+        match data.get(i) {
+            Some(&count) if count as usize == len => Ok(()),
+            _ => Err(invalid),
+        }
+    };
+
+    let req = match fc {
+        0x01 => Request::ReadCoils(word(0)?, quantity(2, MAX_READ_COILS)?),
+        0x02 => Request::ReadDiscreteInputs(word(0)?, quantity(2, MAX_READ_COILS)?),
+        0x03 => Request::ReadHoldingRegisters(word(0)?, quantity(2, MAX_READ_REGISTERS)?),
+        0x04 => Request::ReadInputRegisters(word(0)?, quantity(2, MAX_READ_REGISTERS)?),
+        0x05 => match word(2)? {
+            0xFF00 => Request::WriteSingleCoil(word(0)?, true),
+            0x0000 => Request::WriteSingleCoil(word(0)?, false),
+            _ => return Err(invalid),
+        },
+        0x06 => Request::WriteSingleRegister(word(0)?, word(2)?),
+        0x0F => {
+            let cnt = quantity(2, MAX_WRITE_COILS)?;
+            byte_count(4, (cnt as usize).div_ceil(8))?;
+            let bytes = data.get(5..).ok_or(invalid)?;
+            if bytes.len() * 8 < cnt as usize {
+                return Err(invalid);
+            }
+            Request::WriteMultipleCoils(word(0)?, Cow::Owned(unpack_coils(bytes, cnt)))
+        }
+        0x10 => {
+            let cnt = quantity(2, MAX_WRITE_REGISTERS)?;
+            byte_count(4, cnt as usize * 2)?;
+            let words = (0..cnt as usize)
+                .map(|i| word(5 + 2 * i))
+                .collect::<Result<Vec<_>, _>>()?;
+            Request::WriteMultipleRegisters(word(0)?, Cow::Owned(words))
+        }
+        0x16 => Request::MaskWriteRegister(word(0)?, word(2)?, word(4)?),
+        0x17 => {
+            let read_cnt = quantity(2, MAX_READ_REGISTERS)?;
+            let cnt = quantity(6, MAX_READ_WRITE_REGISTERS)?;
+            byte_count(8, cnt as usize * 2)?;
+            let words = (0..cnt as usize)
+                .map(|i| word(9 + 2 * i))
+                .collect::<Result<Vec<_>, _>>()?;
+            Request::ReadWriteMultipleRegisters(word(0)?, read_cnt, word(4)?, Cow::Owned(words))
+        }
+        _ => Request::Custom(fc, Cow::Owned(data.to_vec())),
+    };
+
+    Ok(req)
+}
+
+/// Encode the response (or exception) PDU to the request with `fc` function code.
+///
+/// A response whose values do not fit in the PDU is encoded as [Exception::ServerDeviceFailure].
+pub(crate) fn encode_response(fc: u8, result: &Result<Response, Exception>) -> Vec<u8> {
+    let response = match result {
+        Ok(response) => response,
+        Err(exception) => return vec![fc | 0x80, exception_code(*exception)],
+    };
+    let too_long = || encode_response(fc, &Err(Exception::ServerDeviceFailure));
+
+    let mut pdu = vec![fc];
+    match response {
+        Response::ReadCoils(coils) | Response::ReadDiscreteInputs(coils) => {
+            let bytes = pack_coils(coils);
+            let Ok(byte_count) = u8::try_from(bytes.len()) else {
+                return too_long();
+            };
+            pdu.push(byte_count);
+            pdu.extend(bytes);
+        }
+        Response::ReadHoldingRegisters(words)
+        | Response::ReadInputRegisters(words)
+        | Response::ReadWriteMultipleRegisters(words) => {
+            let Ok(byte_count) = u8::try_from(words.len() * 2) else {
+                return too_long();
+            };
+            pdu.push(byte_count);
+            pdu.extend(words.iter().flat_map(|word| word.to_be_bytes()));
+        }
+        Response::WriteSingleCoil(addr, coil) => {
+            pdu.extend(addr.to_be_bytes());
+            pdu.extend(if *coil { [0xFF, 0x00] } else { [0x00, 0x00] });
+        }
+        Response::WriteSingleRegister(addr, word) => {
+            pdu.extend(addr.to_be_bytes());
+            pdu.extend(word.to_be_bytes());
+        }
+        Response::WriteMultipleCoils(addr, cnt) | Response::WriteMultipleRegisters(addr, cnt) => {
+            pdu.extend(addr.to_be_bytes());
+            pdu.extend(cnt.to_be_bytes());
+        }
+        Response::MaskWriteRegister(addr, and_mask, or_mask) => {
+            pdu.extend(addr.to_be_bytes());
+            pdu.extend(and_mask.to_be_bytes());
+            pdu.extend(or_mask.to_be_bytes());
+        }
+        Response::Custom(_, data) => {
+            pdu.extend_from_slice(data);
+        }
+    }
+
+    pdu
+}
+
+/// Encode Modbus TCP (MBAP) frame from transaction id, unit id and PDU.
+pub(crate) fn encode_mbap_frame(transaction_id: u16, unit_id: u8, pdu: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(MBAP_HEADER_LEN + pdu.len());
+    frame.extend(transaction_id.to_be_bytes());
+    frame.extend(0u16.to_be_bytes());
+    frame.extend((pdu.len() as u16 + 1).to_be_bytes());
+    frame.push(unit_id);
+    frame.extend_from_slice(pdu);
+    frame
+}
+
+/// Decode MBAP header into transaction id, unit id and the length of the following PDU.
+pub(crate) fn decode_mbap_header(header: &[u8]) -> io::Result<(u16, u8, usize)> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Invalid MBAP header");
+    let header = header.get(..MBAP_HEADER_LEN).ok_or_else(invalid)?;
+    let transaction_id = u16::from_be_bytes([header[0], header[1]]);
+    let protocol_id = u16::from_be_bytes([header[2], header[3]]);
+    let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+    if protocol_id != 0 || !(2..=254).contains(&len) {
+        return Err(invalid());
+    }
+    Ok((transaction_id, header[6], len - 1))
+}
+
+fn pack_coils(coils: &[Coil]) -> Vec<u8> {
+    coils
+        .chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0, |byte, (i, coil)| byte | ((*coil as u8) << i))
+        })
+        .collect()
+}
+
+fn unpack_coils(bytes: &[u8], cnt: u16) -> Vec<Coil> {
+    (0..cnt as usize)
+        .map(|i| bytes[i / 8] & (1 << (i % 8)) != 0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mbap_frame() {
+        let frame = encode_mbap_frame(7, 1, &[0x03, 0x00, 0x05, 0x00, 0x01]);
+        assert_eq!(
+            frame,
+            [0x00, 0x07, 0x00, 0x00, 0x00, 0x06, 0x01, 0x03, 0x00, 0x05, 0x00, 0x01]
+        );
+        assert_eq!(decode_mbap_header(&frame).unwrap(), (7, 1, 5));
+        assert!(decode_mbap_header(&frame[..6]).is_err());
+    }
+
+    #[test]
+    fn test_request_response() {
+        let req = Request::WriteMultipleCoils(3, Cow::Owned(vec![true, false, true]));
+        assert_eq!(decode_request(&encode_request(&req).unwrap()).unwrap(), req);
+
+        let req = Request::ReadCoils(3, 10);
+        let response = Ok(Response::ReadCoils(vec![true; 10]));
+        let pdu = encode_response(0x01, &response);
+        assert_eq!(decode_response(&req, &pdu).unwrap(), response);
+
+        let pdu = encode_response(0x01, &Err(Exception::IllegalDataAddress));
+        assert_eq!(
+            decode_response(&req, &pdu).unwrap(),
+            Err(Exception::IllegalDataAddress)
+        );
+        assert!(decode_response(&Request::ReadInputRegisters(0, 1), &pdu).is_err());
+    }
+
+    #[test]
+    fn test_quantity() {
+        let invalid = Err((0x03, Exception::IllegalDataValue));
+        assert_eq!(decode_request(&[0x03, 0x00, 0x00, 0x00, 0x00]), invalid);
+        assert_eq!(decode_request(&[0x03, 0x00, 0x00, 0x00, 0x7E]), invalid);
+        assert_eq!(
+            decode_request(&[0x03, 0x00, 0x00, 0x00, 0x7D]).unwrap(),
+            Request::ReadHoldingRegisters(0, 125)
+        );
+        assert_eq!(
+            decode_request(&[0x01, 0x00, 0x00, 0x07, 0xD1]),
+            Err((0x01, Exception::IllegalDataValue))
+        );
+        // Byte count mismatching the quantity
+        assert_eq!(
+            decode_request(&[0x10, 0x00, 0x00, 0x00, 0x01, 0x04, 0x00, 0x01, 0x00, 0x02]),
+            Err((0x10, Exception::IllegalDataValue))
+        );
+
+        let words = vec![0; 128];
+        let req = Request::WriteMultipleRegisters(0, Cow::Borrowed(&words));
+        assert_eq!(
+            encode_request(&req).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        let response = Ok(Response::ReadHoldingRegisters(words));
+        assert_eq!(
+            encode_response(0x03, &response),
+            encode_response(0x03, &Err(Exception::ServerDeviceFailure))
+        );
+    }
+
+    #[test]
+    fn test_coils() {
+        let coils = [true, false, true, true, false, false, false, false, true];
+        let bytes = pack_coils(&coils);
+        assert_eq!(bytes, vec![0b0000_1101, 0b0000_0001]);
+        assert_eq!(unpack_coils(&bytes, coils.len() as u16), coils);
+    }
+}
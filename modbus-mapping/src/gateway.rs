@@ -0,0 +1,355 @@
+use std::{
+    collections::HashMap, fmt::Debug, future::Future, io, net::SocketAddr, sync::Arc,
+    time::Duration,
+};
+
+use futures::future::{self, BoxFuture, FutureExt};
+use tokio::{
+    sync::Mutex,
+    time::{timeout, Instant},
+};
+use tokio_modbus::{
+    client::{Client, Context},
+    prelude::SlaveRequest,
+    slave::{Slave, SlaveId},
+    Exception, Request, Response,
+};
+
+use crate::simulator::{Reply, Simulation, TcpSimulator};
+
+/// Default timeout of the forwarded requests.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+/// Default delay after the broadcast requests.
+const DEFAULT_BROADCAST_DELAY: Duration = Duration::from_millis(100);
+
+type Connect<C> = Arc<dyn Fn() -> BoxFuture<'static, io::Result<C>> + Send + Sync>;
+
+/// Cached response of a read request.
+struct Cached {
+    request: Request<'static>,
+    response: Response,
+    at: Instant,
+}
+
+struct Inner<C> {
+    connect: Connect<C>,
+    client: Option<C>,
+    cache: HashMap<SlaveId, Vec<Cached>>,
+}
+
+/// Modbus gateway forwarding the requests by their unit id to the slaves behind `client`, e.g. on RTU bus.
+///
+/// It implements [Simulation], so it is served by [TcpSimulator] (or any other simulator transport)
+/// like a simulated device, see [run_tcp_rtu_gateway].
+/// One request is forwarded at a time. The requests failing by a transport error or timeout
+/// are answered with [Exception::GatewayTargetDevice], the exceptions of the slaves are passed through.
+/// After such a failure the client is dropped, so a late response cannot be taken for the response
+/// to the next request, and connected again by the next request.
+///
+/// The broadcast requests (unit id 0) are forwarded without waiting for a response and left unanswered,
+/// the next request is forwarded after the broadcast delay.
+///
+/// Optionally, the responses to read requests are cached for `max_age`, so the clients polling the same registers
+/// share the forwarded requests. Any other request addressed to a slave clears its cached responses.
+pub struct Gateway<C> {
+    inner: Arc<Mutex<Inner<C>>>,
+    timeout: Duration,
+    slave_timeouts: HashMap<SlaveId, Duration>,
+    broadcast_delay: Duration,
+    cache_max_age: Option<Duration>,
+}
+
+impl Gateway<Context> {
+    /// Create gateway forwarding the requests to the slaves on the serial port at `path`.
+    pub fn rtu(path: &str, baud_rate: u32) -> Self {
+        let path = path.to_owned();
+        Self::new(move || {
+            let builder = tokio_serial::new(&path, baud_rate);
+            let opened = tokio_serial::SerialStream::open(&builder);
+            future::ready(
+                opened
+                    .map(tokio_modbus::client::rtu::attach)
+                    .map_err(io::Error::from),
+            )
+        })
+    }
+}
+
+impl<C: Client + 'static> Gateway<C> {
+    /// Create gateway forwarding the requests by the client connected by `connect` on demand.
+    pub fn new<F, Fut>(connect: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = io::Result<C>> + Send + 'static,
+    {
+        let connect: Connect<C> = Arc::new(move || connect().boxed());
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                connect,
+                client: None,
+                cache: HashMap::new(),
+            })),
+            timeout: DEFAULT_TIMEOUT,
+            slave_timeouts: HashMap::new(),
+            broadcast_delay: DEFAULT_BROADCAST_DELAY,
+            cache_max_age: None,
+        }
+    }
+
+    /// Set timeout of the requests forwarded to the slaves without their own timeout.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set timeout of the requests forwarded to `slave`, e.g. to a slow device.
+    pub fn with_slave_timeout(mut self, slave: Slave, timeout: Duration) -> Self {
+        self.slave_timeouts.insert(slave.into(), timeout);
+        self
+    }
+
+    /// Set the delay after the broadcast requests, giving the slaves time to process them.
+    pub fn with_broadcast_delay(mut self, delay: Duration) -> Self {
+        self.broadcast_delay = delay;
+        self
+    }
+
+    /// Answer the read requests from the responses not older than `max_age`.
+    pub fn with_cache(mut self, max_age: Duration) -> Self {
+        self.cache_max_age = Some(max_age);
+        self
+    }
+
+    async fn forward(
+        inner: Arc<Mutex<Inner<C>>>,
+        req: SlaveRequest<'static>,
+        request_timeout: Duration,
+        broadcast_delay: Duration,
+        cache_max_age: Option<Duration>,
+    ) -> Reply {
+        let mut inner = inner.lock().await;
+        let Inner {
+            connect,
+            client,
+            cache,
+        } = &mut *inner;
+
+        if Slave(req.slave).is_broadcast() {
+            // The broadcast request may change the values of any slave
+            cache.clear();
+            // No slave responds, so the call is expected to time out
+            let _ = Self::call_slave(connect, client, req, broadcast_delay).await;
+            return Reply::NoResponse;
+        }
+
+        let cacheable = cache_max_age.is_some() && is_read(&req.request);
+
+        if let Some(max_age) = cache_max_age {
+            let cached = cache.entry(req.slave).or_default();
+            if cacheable {
+                cached.retain(|cached| cached.at.elapsed() <= max_age);
+                if let Some(cached) = cached.iter().find(|cached| cached.request == req.request) {
+                    return Reply::Response(Ok(cached.response.clone()));
+                }
+            } else {
+                // The cached values may be changed by the request
+                cached.clear();
+            }
+        }
+
+        let result = Self::call_slave(connect, client, req.clone(), request_timeout)
+            .await
+            .unwrap_or(Err(Exception::GatewayTargetDevice));
+        if let (true, Ok(response)) = (cacheable, &result) {
+            cache.entry(req.slave).or_default().push(Cached {
+                request: req.request,
+                response: response.clone(),
+                at: Instant::now(),
+            });
+        }
+        Reply::Response(result)
+    }
+
+    /// Forward `req` by the client, connecting it first if needed, within `duration`.
+    /// The client is dropped after a transport error or timeout.
+    async fn call_slave(
+        connect: &Connect<C>,
+        client: &mut Option<C>,
+        req: SlaveRequest<'static>,
+        duration: Duration,
+    ) -> tokio_modbus::Result<Response> {
+        let calling = async {
+            let client = match client {
+                Some(client) => client,
+                None => client.insert(connect().await?),
+            };
+            client.set_slave(Slave(req.slave));
+            client.call(req.request).await
+        };
+        let result = timeout(duration, calling)
+            .await
+            .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::TimedOut, "Timed out").into()));
+        if result.is_err() {
+            *client = None;
+        }
+        result
+    }
+}
+
+impl<C> Clone for Gateway<C> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            timeout: self.timeout,
+            slave_timeouts: self.slave_timeouts.clone(),
+            broadcast_delay: self.broadcast_delay,
+            cache_max_age: self.cache_max_age,
+        }
+    }
+}
+
+impl<C> Debug for Gateway<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Gateway")
+            .field("timeout", &self.timeout)
+            .field("slave_timeouts", &self.slave_timeouts)
+            .field("broadcast_delay", &self.broadcast_delay)
+            .field("cache_max_age", &self.cache_max_age)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: Client + 'static> Simulation for Gateway<C> {
+    fn call(&self, req: SlaveRequest<'static>) -> BoxFuture<'static, Reply> {
+        let request_timeout = self
+            .slave_timeouts
+            .get(&req.slave)
+            .copied()
+            .unwrap_or(self.timeout);
+        Self::forward(
+            self.inner.clone(),
+            req,
+            request_timeout,
+            self.broadcast_delay,
+            self.cache_max_age,
+        )
+        .boxed()
+    }
+
+    /// The gateway has no state of its own.
+    fn update_state(&self, _elapsed: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(future::ready(()))
+    }
+}
+
+fn is_read(request: &Request) -> bool {
+    matches!(
+        request,
+        Request::ReadCoils(..)
+            | Request::ReadDiscreteInputs(..)
+            | Request::ReadInputRegisters(..)
+            | Request::ReadHoldingRegisters(..)
+    )
+}
+
+/// Utility function to run Modbus TCP to RTU `gateway` at `socket_addr` forever.
+///
+/// The gateway is configured by its builder methods, e.g.
+/// `Gateway::rtu("/dev/ttyUSB0", 9600).with_slave_timeout(Slave(2), Duration::from_secs(3)).with_cache(Duration::from_secs(1))`.
+pub async fn run_tcp_rtu_gateway<C: Client + 'static>(
+    socket_addr: SocketAddr,
+    gateway: Gateway<C>,
+) -> io::Result<()> {
+    TcpSimulator::new(socket_addr, gateway)
+        .run_until(future::pending())
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::testing::replay_simulator;
+    use crate::simulator::{FaultySimulation, LocalClient, MultiSimulator};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_gateway() {
+        let device = replay_simulator([(
+            1,
+            Request::ReadHoldingRegisters(0, 1),
+            Response::ReadHoldingRegisters(vec![42]),
+        )]);
+        let slow = FaultySimulation::new(device.clone())
+            .with_latency(Duration::from_millis(500), Duration::ZERO);
+        let bus = MultiSimulator::new()
+            .with_slave(Slave(1), device.clone())
+            .with_slave(Slave(2), slow);
+        let connections = Arc::new(AtomicUsize::new(0));
+        let connect = {
+            let connections = connections.clone();
+            move || {
+                connections.fetch_add(1, Ordering::SeqCst);
+                future::ready(Ok(LocalClient::new(bus.clone(), Slave(0))))
+            }
+        };
+        let gateway = Gateway::new(connect)
+            .with_slave_timeout(Slave(2), Duration::from_millis(100))
+            .with_cache(Duration::from_secs(1));
+        let call = |slave, request: &Request<'static>| {
+            gateway.call(SlaveRequest {
+                slave,
+                request: request.clone(),
+            })
+        };
+        let read = Request::ReadHoldingRegisters(0, 1);
+
+        assert_eq!(
+            call(1, &read).await,
+            Reply::Response(Ok(Response::ReadHoldingRegisters(vec![42])))
+        );
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+        // The client is connected again after the timeout
+        assert_eq!(
+            call(2, &read).await,
+            Reply::Response(Err(Exception::GatewayTargetDevice))
+        );
+        assert_eq!(
+            call(3, &read).await,
+            Reply::Response(Err(Exception::GatewayTargetDevice))
+        );
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+
+        // The value changed behind the gateway is served from the cache until it expires
+        let write = SlaveRequest {
+            slave: 1,
+            request: Request::WriteSingleRegister(0, 7),
+        };
+        Simulation::call(&device, write).await;
+        assert_eq!(
+            call(1, &read).await,
+            Reply::Response(Ok(Response::ReadHoldingRegisters(vec![42])))
+        );
+        tokio::time::advance(Duration::from_secs(2)).await;
+        assert_eq!(
+            call(1, &read).await,
+            Reply::Response(Ok(Response::ReadHoldingRegisters(vec![7])))
+        );
+
+        // Writes through the gateway clear the cache
+        call(1, &Request::WriteSingleRegister(0, 8)).await;
+        assert_eq!(
+            call(1, &read).await,
+            Reply::Response(Ok(Response::ReadHoldingRegisters(vec![8])))
+        );
+
+        // Broadcasts are left unanswered and clear the whole cache
+        assert_eq!(
+            call(0, &Request::WriteSingleRegister(0, 9)).await,
+            Reply::NoResponse
+        );
+        assert_eq!(
+            call(1, &read).await,
+            Reply::Response(Ok(Response::ReadHoldingRegisters(vec![9])))
+        );
+    }
+}
@@ -0,0 +1,371 @@
+use std::{borrow::Cow, collections::BTreeMap, io};
+
+use tokio_modbus::{client::Client, Exception, Request, Response};
+
+/// Function code of Modbus Encapsulated Interface (MEI) transport.
+pub(crate) const ENCAPSULATED_INTERFACE_TRANSPORT: u8 = 0x2B;
+/// MEI type of Read Device Identification request.
+pub(crate) const READ_DEVICE_IDENTIFICATION: u8 = 0x0E;
+/// Maximum size of Read Device Identification response objects list to fit into a single PDU.
+const MAX_OBJECTS_SIZE: usize = 253 - 7;
+
+/// Device identification object id.
+pub type ObjectId = u8;
+
+// Basic category objects (mandatory)
+pub const VENDOR_NAME: ObjectId = 0x00;
+pub const PRODUCT_CODE: ObjectId = 0x01;
+pub const MAJOR_MINOR_REVISION: ObjectId = 0x02;
+// Regular category objects (optional)
+pub const VENDOR_URL: ObjectId = 0x03;
+pub const PRODUCT_NAME: ObjectId = 0x04;
+pub const MODEL_NAME: ObjectId = 0x05;
+pub const USER_APPLICATION_NAME: ObjectId = 0x06;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Read Device ID code selecting the access type and the objects category
+pub enum ReadDeviceIdCode {
+    /// Stream access to basic objects `0x00..=0x02`
+    Basic = 0x01,
+    /// Stream access to basic and regular objects `0x00..=0x7F`
+    Regular = 0x02,
+    /// Stream access to all objects `0x00..=0xFF`
+    Extended = 0x03,
+    /// Individual access to a single object
+    Specific = 0x04,
+}
+
+impl ReadDeviceIdCode {
+    /// Last object id of the category accessible by the code.
+    pub fn last_object_id(&self) -> ObjectId {
+        match self {
+            ReadDeviceIdCode::Basic => 0x02,
+            ReadDeviceIdCode::Regular => 0x7F,
+            ReadDeviceIdCode::Extended | ReadDeviceIdCode::Specific => 0xFF,
+        }
+    }
+}
+
+impl TryFrom<u8> for ReadDeviceIdCode {
+    type Error = Exception;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(ReadDeviceIdCode::Basic),
+            0x02 => Ok(ReadDeviceIdCode::Regular),
+            0x03 => Ok(ReadDeviceIdCode::Extended),
+            0x04 => Ok(ReadDeviceIdCode::Specific),
+            _ => Err(Exception::IllegalDataValue),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Device identification objects read by (or served to) Read Device Identification (FC43/14) request
+pub struct DeviceIdentification(BTreeMap<ObjectId, Vec<u8>>);
+
+impl DeviceIdentification {
+    /// Create basic device identification with the mandatory objects.
+    pub fn new(
+        vendor_name: &str,
+        product_code: &str,
+        major_minor_revision: &str,
+    ) -> io::Result<Self> {
+        let mut new = Self::default();
+        new.insert(VENDOR_NAME, vendor_name.as_bytes().to_vec())?;
+        new.insert(PRODUCT_CODE, product_code.as_bytes().to_vec())?;
+        new.insert(
+            MAJOR_MINOR_REVISION,
+            major_minor_revision.as_bytes().to_vec(),
+        )?;
+        Ok(new)
+    }
+
+    /// Insert object `value` with `object_id`.
+    /// Fail with [io::ErrorKind::InvalidInput] error if the value does not fit into a single response.
+    pub fn insert(&mut self, object_id: ObjectId, value: Vec<u8>) -> io::Result<()> {
+        if value.len() > MAX_OBJECTS_SIZE - 2 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Device identification object {object_id:#04x} is too long"),
+            ));
+        }
+        self.0.insert(object_id, value);
+        Ok(())
+    }
+
+    /// Get raw object value.
+    pub fn get(&self, object_id: ObjectId) -> Option<&[u8]> {
+        self.0.get(&object_id).map(Vec::as_slice)
+    }
+
+    /// Get object value as (lossy) UTF-8 string.
+    pub fn get_str(&self, object_id: ObjectId) -> Option<Cow<'_, str>> {
+        self.get(object_id).map(String::from_utf8_lossy)
+    }
+
+    pub fn vendor_name(&self) -> Option<Cow<'_, str>> {
+        self.get_str(VENDOR_NAME)
+    }
+
+    pub fn product_code(&self) -> Option<Cow<'_, str>> {
+        self.get_str(PRODUCT_CODE)
+    }
+
+    pub fn major_minor_revision(&self) -> Option<Cow<'_, str>> {
+        self.get_str(MAJOR_MINOR_REVISION)
+    }
+
+    pub fn vendor_url(&self) -> Option<Cow<'_, str>> {
+        self.get_str(VENDOR_URL)
+    }
+
+    pub fn product_name(&self) -> Option<Cow<'_, str>> {
+        self.get_str(PRODUCT_NAME)
+    }
+
+    pub fn model_name(&self) -> Option<Cow<'_, str>> {
+        self.get_str(MODEL_NAME)
+    }
+
+    pub fn user_application_name(&self) -> Option<Cow<'_, str>> {
+        self.get_str(USER_APPLICATION_NAME)
+    }
+
+    /// Iterate over all objects ordered by object id.
+    pub fn iter(&self) -> impl Iterator<Item = (ObjectId, &[u8])> {
+        self.0.iter().map(|(id, value)| (*id, value.as_slice()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl DeviceIdentification {
+    /// Read all objects supported by the device walking basic, regular and extended categories.
+    pub async fn from_client(client: &mut dyn Client) -> tokio_modbus::Result<Self> {
+        let mut new = Self::default();
+
+        let conformity_level = match new
+            .update_from_stream(client, ReadDeviceIdCode::Basic)
+            .await?
+        {
+            Ok(conformity_level) => conformity_level,
+            Err(exc) => return Ok(Err(exc)),
+        };
+
+        // The lower bits of conformity level give the highest supported category
+        for code in [ReadDeviceIdCode::Regular, ReadDeviceIdCode::Extended] {
+            if conformity_level & 0x7F < code as u8 {
+                break;
+            }
+            if let Err(exc) = new.update_from_stream(client, code).await? {
+                return Ok(Err(exc));
+            }
+        }
+
+        Ok(Ok(new))
+    }
+
+    /// Read objects of the `code` category by stream access and return the device conformity level.
+    pub async fn update_from_stream(
+        &mut self,
+        client: &mut dyn Client,
+        code: ReadDeviceIdCode,
+    ) -> tokio_modbus::Result<u8> {
+        let mut object_id = VENDOR_NAME;
+        loop {
+            let data = match call(client, code, object_id).await? {
+                Ok(data) => data,
+                Err(exc) => return Ok(Err(exc)),
+            };
+            let (conformity_level, next_object_id, objects) = decode_response(&data)?;
+            let is_empty = objects.is_empty();
+            for (id, value) in objects {
+                self.0.insert(id, value);
+            }
+
+            match next_object_id {
+                // Stop on a misbehaving device to prevent the endless loop
+                Some(next_object_id) if !is_empty && next_object_id > object_id => {
+                    object_id = next_object_id
+                }
+                _ => return Ok(Ok(conformity_level)),
+            }
+        }
+    }
+
+    /// Read a single object by individual access.
+    pub async fn read_object(
+        client: &mut dyn Client,
+        object_id: ObjectId,
+    ) -> tokio_modbus::Result<Vec<u8>> {
+        let data = match call(client, ReadDeviceIdCode::Specific, object_id).await? {
+            Ok(data) => data,
+            Err(exc) => return Ok(Err(exc)),
+        };
+        let (_, _, objects) = decode_response(&data)?;
+        match objects.into_iter().find(|(id, _)| *id == object_id) {
+            Some((_, value)) => Ok(Ok(value)),
+            None => Err(invalid_data("requested object is missing in the response").into()),
+        }
+    }
+}
+
+#[cfg(feature = "simulator")]
+impl DeviceIdentification {
+    /// Encode the response data (without function code) to the server side request.
+    pub(crate) fn encode_response(
+        &self,
+        read_device_id_code: u8,
+        object_id: ObjectId,
+    ) -> Result<Vec<u8>, Exception> {
+        if self.0.is_empty() {
+            return Err(Exception::IllegalFunction);
+        }
+
+        let code = ReadDeviceIdCode::try_from(read_device_id_code)?;
+        let mut objects = vec![];
+        let mut next_object_id = None;
+        match code {
+            ReadDeviceIdCode::Specific => {
+                let value = self
+                    .0
+                    .get(&object_id)
+                    .ok_or(Exception::IllegalDataAddress)?;
+                objects.push((object_id, value));
+            }
+            _ => {
+                let last_object_id = code.last_object_id();
+                // Restart at the beginning for unknown object id
+                let first_object_id =
+                    if object_id <= last_object_id && self.0.contains_key(&object_id) {
+                        object_id
+                    } else {
+                        VENDOR_NAME
+                    };
+                let mut size = 0;
+                for (id, value) in self.0.range(first_object_id..=last_object_id) {
+                    size += 2 + value.len();
+                    if size > MAX_OBJECTS_SIZE {
+                        next_object_id = Some(*id);
+                        break;
+                    }
+                    objects.push((*id, value));
+                }
+            }
+        }
+
+        let mut data = vec![
+            READ_DEVICE_IDENTIFICATION,
+            read_device_id_code,
+            self.conformity_level(),
+            if next_object_id.is_some() { 0xFF } else { 0x00 },
+            next_object_id.unwrap_or(0x00),
+            objects.len() as u8,
+        ];
+        for (id, value) in objects {
+            data.push(id);
+            data.push(value.len() as u8);
+            data.extend_from_slice(value);
+        }
+
+        Ok(data)
+    }
+
+    fn conformity_level(&self) -> u8 {
+        // Individual access is always supported
+        match self.0.keys().next_back() {
+            Some(0x80..) => 0x83,
+            Some(0x03..) => 0x82,
+            _ => 0x81,
+        }
+    }
+}
+
+async fn call(
+    client: &mut dyn Client,
+    code: ReadDeviceIdCode,
+    object_id: ObjectId,
+) -> tokio_modbus::Result<Vec<u8>> {
+    let data = [READ_DEVICE_IDENTIFICATION, code as u8, object_id];
+    let request = Request::Custom(ENCAPSULATED_INTERFACE_TRANSPORT, Cow::Borrowed(&data));
+    match client.call(request).await? {
+        Ok(Response::Custom(ENCAPSULATED_INTERFACE_TRANSPORT, data)) => Ok(Ok(data.to_vec())),
+        Ok(_) => Err(invalid_data("unexpected response function code").into()),
+        Err(exc) => Ok(Err(exc)),
+    }
+}
+
+type Objects = Vec<(ObjectId, Vec<u8>)>;
+
+/// Decode the response data into conformity level, next object id (if more follows) and objects.
+fn decode_response(data: &[u8]) -> io::Result<(u8, Option<ObjectId>, Objects)> {
+    let [READ_DEVICE_IDENTIFICATION, _code, conformity_level, more_follows, next_object_id, number_of_objects, rest @ ..] =
+        data
+    else {
+        return Err(invalid_data("invalid response header"));
+    };
+
+    let mut objects = Vec::with_capacity(*number_of_objects as usize);
+    let mut rest = rest;
+    for _ in 0..*number_of_objects {
+        let [id, len, tail @ ..] = rest else {
+            return Err(invalid_data("truncated object header"));
+        };
+        if tail.len() < *len as usize {
+            return Err(invalid_data("truncated object value"));
+        }
+        let (value, tail) = tail.split_at(*len as usize);
+        objects.push((*id, value.to_vec()));
+        rest = tail;
+    }
+
+    let next_object_id = (*more_follows == 0xFF).then_some(*next_object_id);
+
+    Ok((*conformity_level, next_object_id, objects))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Read Device Identification: {msg}"),
+    )
+}
+
+#[cfg(all(test, feature = "simulator"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_identification_stream() {
+        let mut device_identification = DeviceIdentification::new("vendor", "code", "1.0").unwrap();
+        device_identification.insert(0x80, vec![0; 240]).unwrap();
+        assert!(device_identification.insert(0x81, vec![0; 245]).is_err());
+
+        // Basic stream does not contain the extended object
+        let data = device_identification.encode_response(0x01, 0x00).unwrap();
+        let (conformity_level, next_object_id, objects) = decode_response(&data).unwrap();
+        assert_eq!((conformity_level, next_object_id), (0x83, None));
+        assert_eq!(objects.len(), 3);
+
+        // Extended stream does not fit into a single response
+        let data = device_identification.encode_response(0x03, 0x00).unwrap();
+        let (_, next_object_id, objects) = decode_response(&data).unwrap();
+        assert_eq!((next_object_id, objects.len()), (Some(0x80), 3));
+        let data = device_identification.encode_response(0x03, 0x80).unwrap();
+        let (_, next_object_id, objects) = decode_response(&data).unwrap();
+        assert_eq!((next_object_id, objects.len()), (None, 1));
+
+        // Individual access
+        assert_eq!(
+            device_identification.encode_response(0x04, 0x02).unwrap(),
+            vec![0x0E, 0x04, 0x83, 0x00, 0x00, 1, 0x02, 3, b'1', b'.', b'0']
+        );
+        assert!(matches!(
+            device_identification.encode_response(0x04, 0x03),
+            Err(Exception::IllegalDataAddress)
+        ));
+    }
+}
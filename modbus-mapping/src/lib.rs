@@ -26,17 +26,41 @@
 //! - `max_cnt_per_request` - maximum number of registers to read in a single Modbus request; default value is `123` which is the maximumum allowed value
 //! - `allow_register_gaps` - an optimization flag to allow Modbus client to read longer register blocks which possibly contain unrequested (or undefined) registers in between the required ones.
 //!   If `true`, the client makes less requests but read more data. Otherwise, if `false`, the client makes more requests but read only the necessary data.
+//! - `forbidden` - comma separated addresses and address ranges which are never read as gaps between the fields, e.g. `"100..120, 130..=140, 200"`, for devices failing on reads of unmapped registers.
 //!
 //! The `modbus_doc` attribute is to create documentation (by adding doc attribute) from `modbus` field attributes information.
 
+/// Clients wrapping tokio-modbus clients, e.g. [`client::ManagedClient`] reconnecting with backoff
+pub mod client;
 /// Utilities for encoding from and decoding to Modbus registers
 pub mod codec;
 /// Core traits to read from and write to Modbus registers
 pub mod core;
+mod frame;
+/// Modbus TCP to RTU gateway forwarding the requests by unit id
+#[cfg(feature = "simulator")]
+pub mod gateway;
+/// Read Device Identification (FC43/14) objects and client helpers
+pub mod identification;
+/// JSON bridge publishing register map types to MQTT and applying received setpoints
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+/// Read requests merged across several register map types of the same slave
+pub mod plan;
+/// Periodic polling of register maps publishing timestamped snapshots
+pub mod poller;
+/// Caching proxy serving polled register maps to many Modbus TCP clients
+#[cfg(feature = "simulator")]
+pub mod proxy;
+/// Recording of Modbus client traffic for later replay
+pub mod record;
 
 /// Traits and utilities to create device simulator (based on tokio-modbus [servers examples](https://github.com/slowtec/tokio-modbus/tree/main/examples))
 #[cfg(feature = "simulator")]
 pub mod simulator;
+/// TLS connector and configuration of Modbus/TCP Security
+#[cfg(feature = "tls")]
+pub mod tls;
 
 pub mod derive {
     /// Re-export.
@@ -0,0 +1,194 @@
+use std::io;
+
+use rumqttc::{AsyncClient, Publish, QoS};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+use tokio_modbus::client::Writer;
+
+use crate::core::{HoldingRegisterMap, InputRegisterMap};
+
+/// Serialize `value` to JSON object with the fields of `units` as `{"value": ..., "unit": ...}` objects.
+pub fn to_json<T: Serialize>(value: &T, units: &[(&str, &str)]) -> serde_json::Result<Value> {
+    let mut json = serde_json::to_value(value)?;
+    if let Value::Object(fields) = &mut json {
+        for (name, unit) in units {
+            if let Some(field) = fields.get_mut(*name) {
+                let mut with_unit = Map::new();
+                with_unit.insert("value".to_string(), field.take());
+                with_unit.insert("unit".to_string(), Value::String(unit.to_string()));
+                *field = Value::Object(with_unit);
+            }
+        }
+    }
+
+    Ok(json)
+}
+
+/// Copy of `value` with the fields set by JSON object `json`.
+///
+/// The fields are given either as plain values or as `{"value": ...}` objects published by [to_json],
+/// the missing fields are left unchanged. Fails on the names which are not fields of `value`.
+pub fn merge_json<T: Serialize + DeserializeOwned>(
+    value: &T,
+    json: &[u8],
+) -> serde_json::Result<T> {
+    let mut merged = serde_json::to_value(value)?;
+    let Value::Object(changes) = serde_json::from_slice(json)? else {
+        return Err(serde::de::Error::custom("Expected JSON object"));
+    };
+    if let Value::Object(fields) = &mut merged {
+        for (name, change) in changes {
+            let change = match change {
+                Value::Object(mut with_unit) if with_unit.contains_key("value") => {
+                    with_unit.remove("value").unwrap_or_default()
+                }
+                change => change,
+            };
+            let Some(field) = fields.get_mut(&name) else {
+                return Err(serde::de::Error::custom(format!("Unknown field `{name}`")));
+            };
+            *field = change;
+        }
+    }
+
+    serde_json::from_value(merged)
+}
+
+#[derive(Debug, Clone)]
+/// Bridge publishing the register map types as JSON to MQTT topics `{device}/{name}`
+/// and applying the setpoints received on `{device}/{name}/set` topics.
+///
+/// The MQTT event loop of the client is polled by the caller, which passes the received publications to [MqttBridge::apply_setpoint].
+pub struct MqttBridge {
+    mqtt: AsyncClient,
+    device: String,
+    qos: QoS,
+}
+
+impl MqttBridge {
+    /// Create bridge publishing the types of `device` by `mqtt` client.
+    pub fn new(mqtt: AsyncClient, device: impl Into<String>) -> Self {
+        Self {
+            mqtt,
+            device: device.into(),
+            qos: QoS::AtLeastOnce,
+        }
+    }
+
+    /// Set quality of service of the publications and subscriptions; at least once by default.
+    pub fn with_qos(mut self, qos: QoS) -> Self {
+        self.qos = qos;
+        self
+    }
+
+    /// Topic of type `name`.
+    pub fn topic(&self, name: &str) -> String {
+        format!("{}/{}", self.device, name)
+    }
+
+    /// Setpoint topic of type `name`.
+    pub fn setpoint_topic(&self, name: &str) -> String {
+        format!("{}/{}/set", self.device, name)
+    }
+
+    /// Publish input registers `value` with units as type `name`.
+    pub async fn publish_input_registers<T>(&self, name: &str, value: &T) -> io::Result<()>
+    where
+        T: InputRegisterMap + Serialize,
+    {
+        self.publish(name, to_json(value, &T::field_units())).await
+    }
+
+    /// Publish holding registers `value` with units as type `name`.
+    pub async fn publish_holding_registers<T>(&self, name: &str, value: &T) -> io::Result<()>
+    where
+        T: HoldingRegisterMap + Serialize,
+    {
+        self.publish(name, to_json(value, &T::field_units())).await
+    }
+
+    async fn publish(&self, name: &str, json: serde_json::Result<Value>) -> io::Result<()> {
+        let payload = serde_json::to_vec(&json?)?;
+        self.mqtt
+            .publish(self.topic(name), self.qos, false, payload)
+            .await
+            .map_err(io::Error::other)
+    }
+
+    /// Subscribe to the setpoint topic of type `name`.
+    pub async fn subscribe_setpoints(&self, name: &str) -> io::Result<()> {
+        self.mqtt
+            .subscribe(self.setpoint_topic(name), self.qos)
+            .await
+            .map_err(io::Error::other)
+    }
+
+    /// Apply JSON setpoint of `publish` to holding registers `value` of type `name`
+    /// and write them by `client`. Return `false` if `publish` is not on the setpoint topic.
+    ///
+    /// The value is changed only if the setpoint is written successfully.
+    pub async fn apply_setpoint<T>(
+        &self,
+        name: &str,
+        value: &mut T,
+        publish: &Publish,
+        client: &mut dyn Writer,
+    ) -> tokio_modbus::Result<bool>
+    where
+        T: HoldingRegisterMap + Serialize + DeserializeOwned,
+    {
+        if publish.topic != self.setpoint_topic(name) {
+            return Ok(Ok(false));
+        }
+        let setpoint = merge_json(value, &publish.payload).map_err(io::Error::from)?;
+        if let Err(exc) = setpoint.write_to_registers(client).await? {
+            return Ok(Err(exc));
+        }
+        *value = setpoint;
+
+        Ok(Ok(true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Battery {
+        power: f32,
+        enabled: bool,
+    }
+
+    #[test]
+    fn test_json() {
+        let battery = Battery {
+            power: 100.0,
+            enabled: false,
+        };
+        assert_eq!(
+            to_json(&battery, &[("power", "W")]).unwrap(),
+            json!({"power": {"value": 100.0, "unit": "W"}, "enabled": false})
+        );
+
+        assert_eq!(
+            merge_json(&battery, br#"{"power": {"value": -50.0, "unit": "W"}}"#).unwrap(),
+            Battery {
+                power: -50.0,
+                enabled: false
+            }
+        );
+        assert_eq!(
+            merge_json(&battery, br#"{"enabled": true}"#).unwrap(),
+            Battery {
+                power: 100.0,
+                enabled: true
+            }
+        );
+        assert!(merge_json(&battery, br#"{"power": "high"}"#).is_err());
+        assert!(merge_json(&battery, br#"{"pwer": 5}"#).is_err());
+        assert!(merge_json(&battery, b"[]").is_err());
+    }
+}
@@ -0,0 +1,366 @@
+use std::ops::Range;
+
+use tokio_modbus::{client::Reader, Address, Exception, Quantity};
+
+use crate::codec::Word;
+
+/// Maximum number of registers allowed in a single read request.
+const MAX_CNT_PER_REQUEST: Quantity = 123;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Plan of read requests covering registers of several register map types of the same slave,
+/// e.g. `[Measurements::field_registers(), Counters::field_registers()].concat()`.
+///
+/// Fields are merged into blocks of at most `max_cnt_per_request` registers,
+/// possibly with up to `max_gap` unmapped registers in between, unless some of them are forbidden.
+/// The words read by the plan are distributed to the types by their `update_from_words` method.
+///
+/// In adaptive mode, a block failing with [Exception::IllegalDataAddress] is split and read again,
+/// down to single fields. The fields failing on their own are learned as unreadable:
+/// they are left out of the read words and never read again, neither as a gap between other fields.
+/// If the split parts of a failed block are read without failure, e.g. because of an unreadable gap between them,
+/// the fields are no longer merged across the split.
+/// The adaptive reads are done by the plan only: the derived `update_from_input_registers` and `update_from_holding_registers`
+/// still fail as a whole, read through the plan and update the types by `update_from_available_words` instead.
+pub struct ReadPlan {
+    /// Sorted non-overlapping field register ranges
+    fields: Vec<Range<u32>>,
+    max_cnt_per_request: u32,
+    max_gap: u32,
+    forbidden: Vec<Range<u32>>,
+    adaptive: bool,
+    unreadable: Vec<Range<u32>>,
+    /// Field starts learned in adaptive mode to begin a new block
+    splits: Vec<u32>,
+    blocks: Vec<Range<u32>>,
+}
+
+impl ReadPlan {
+    /// Plan reads of the field registers `(addr, cnt)`, never reading the `forbidden` registers `(addr, cnt)`
+    /// as a gap between the fields, e.g. those of the `forbidden` attribute key of the register map types.
+    pub fn new(
+        registers: impl IntoIterator<Item = (Address, Quantity)>,
+        forbidden: impl IntoIterator<Item = (Address, Quantity)>,
+        max_cnt_per_request: Quantity,
+        max_gap: Quantity,
+    ) -> Self {
+        let mut ranges = registers
+            .into_iter()
+            .filter(|(_, cnt)| *cnt > 0)
+            .map(|(addr, cnt)| addr as u32..addr as u32 + cnt as u32)
+            .collect::<Vec<Range<u32>>>();
+        ranges.sort_by_key(|range| range.start);
+
+        let mut fields: Vec<Range<u32>> = Vec::new();
+        for range in ranges {
+            match fields.last_mut() {
+                Some(field) if range.start < field.end => field.end = field.end.max(range.end),
+                _ => fields.push(range),
+            }
+        }
+
+        let mut plan = Self {
+            fields,
+            max_cnt_per_request: max_cnt_per_request.clamp(1, MAX_CNT_PER_REQUEST) as u32,
+            max_gap: max_gap as u32,
+            forbidden: forbidden
+                .into_iter()
+                .map(|(addr, cnt)| addr as u32..addr as u32 + cnt as u32)
+                .collect(),
+            adaptive: false,
+            unreadable: Vec::new(),
+            splits: Vec::new(),
+            blocks: Vec::new(),
+        };
+        plan.replan();
+        plan
+    }
+
+    /// Split the blocks failing with [Exception::IllegalDataAddress] and learn the unreadable fields.
+    pub fn with_adaptive(mut self, adaptive: bool) -> Self {
+        self.adaptive = adaptive;
+        self
+    }
+
+    fn replan(&mut self) {
+        let mut blocks: Vec<Range<u32>> = Vec::new();
+        for field in &self.fields {
+            if self.unreadable.contains(field) {
+                continue;
+            }
+            match blocks.last_mut() {
+                Some(block)
+                    if field.start <= block.end + self.max_gap
+                        && field.end - block.start <= self.max_cnt_per_request
+                        && !self.splits.contains(&field.start)
+                        && !self
+                            .unreadable
+                            .iter()
+                            .chain(&self.forbidden)
+                            .any(|range| range.start < field.start && block.end < range.end) =>
+                {
+                    block.end = field.end;
+                }
+                _ => blocks.push(field.clone()),
+            }
+        }
+        self.blocks = blocks;
+    }
+
+    /// The planned read requests `(addr, cnt)`.
+    pub fn blocks(&self) -> Vec<(Address, Quantity)> {
+        self.blocks.iter().map(to_addr_cnt).collect()
+    }
+
+    /// Field registers `(addr, cnt)` learned as unreadable in adaptive mode.
+    pub fn unreadable(&self) -> Vec<(Address, Quantity)> {
+        self.unreadable.iter().map(to_addr_cnt).collect()
+    }
+
+    /// Read input registers of the planned blocks.
+    pub async fn read_input_registers(
+        &mut self,
+        client: &mut dyn Reader,
+    ) -> tokio_modbus::Result<RegisterWords> {
+        self.read(client, false).await
+    }
+
+    /// Read holding registers of the planned blocks.
+    pub async fn read_holding_registers(
+        &mut self,
+        client: &mut dyn Reader,
+    ) -> tokio_modbus::Result<RegisterWords> {
+        self.read(client, true).await
+    }
+
+    async fn read(
+        &mut self,
+        client: &mut dyn Reader,
+        holding: bool,
+    ) -> tokio_modbus::Result<RegisterWords> {
+        let mut words = RegisterWords::default();
+        let mut learned = false;
+        for block in self.blocks.clone() {
+            // Fields of the block to read at once, the last one is read first
+            let mut pending = vec![self
+                .fields
+                .iter()
+                .filter(|field| block.start <= field.start && field.end <= block.end)
+                .cloned()
+                .collect::<Vec<_>>()];
+            // Failed groups of fields and the start of their second part
+            let mut failed = Vec::new();
+
+            while let Some(mut fields) = pending.pop() {
+                let (addr, cnt) = to_addr_cnt(&(fields[0].start..fields[fields.len() - 1].end));
+                let result = if holding {
+                    client.read_holding_registers(addr, cnt).await?
+                } else {
+                    client.read_input_registers(addr, cnt).await?
+                };
+                match result {
+                    Ok(block) => words.0.push((addr, block)),
+                    Err(Exception::IllegalDataAddress) if self.adaptive => {
+                        if fields.len() == 1 {
+                            self.unreadable.push(fields.remove(0));
+                            learned = true;
+                        } else {
+                            let second = fields.split_off(fields.len() / 2);
+                            failed.push((
+                                fields[0].start..second[second.len() - 1].end,
+                                second[0].start,
+                            ));
+                            pending.push(second);
+                            pending.push(fields);
+                        }
+                    }
+                    Err(exc) => return Ok(Err(exc)),
+                }
+            }
+
+            // The failure of a group not explained by the unreadable fields or splits learned within it
+            // is caused by reading across its split, the inner groups are checked first
+            for (group, split) in failed.into_iter().rev() {
+                let explained = self
+                    .unreadable
+                    .iter()
+                    .any(|field| group.start <= field.start && field.end <= group.end)
+                    || self
+                        .splits
+                        .iter()
+                        .any(|start| group.start < *start && *start < group.end);
+                if !explained {
+                    self.splits.push(split);
+                    learned = true;
+                }
+            }
+        }
+
+        if learned {
+            self.unreadable.sort_by_key(|range| range.start);
+            self.replan();
+        }
+
+        Ok(Ok(words))
+    }
+}
+
+fn to_addr_cnt(range: &Range<u32>) -> (Address, Quantity) {
+    (
+        range.start as Address,
+        (range.end - range.start) as Quantity,
+    )
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Register words read in blocks `(addr, words)` ordered by the address.
+pub struct RegisterWords(pub Vec<(Address, Vec<Word>)>);
+
+impl RegisterWords {
+    /// Words of `cnt` registers from `addr`, if read in a single block.
+    pub fn get(&self, addr: Address, cnt: Quantity) -> Option<&[Word]> {
+        let index = self
+            .0
+            .partition_point(|(start, _)| *start <= addr)
+            .checked_sub(1)?;
+        let (start, words) = &self.0[index];
+        let offset = (addr - start) as usize;
+        words.get(offset..offset + cnt as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_plan() {
+        let registers = [(0, 2), (2, 2), (10, 1), (8, 2), (12, 4), (40, 1), (2, 1)];
+        // Overlapping fields are merged
+        assert_eq!(
+            ReadPlan::new(registers, [], 123, 0).fields,
+            [0..2, 2..4, 8..10, 10..11, 12..16, 40..41]
+        );
+        assert_eq!(
+            ReadPlan::new(registers, [], 123, 0).blocks(),
+            [(0, 4), (8, 3), (12, 4), (40, 1)]
+        );
+        assert_eq!(
+            ReadPlan::new(registers, [], 123, 4).blocks(),
+            [(0, 16), (40, 1)]
+        );
+        assert_eq!(
+            ReadPlan::new(registers, [], 10, 4).blocks(),
+            [(0, 10), (10, 6), (40, 1)]
+        );
+        // Gaps of the forbidden registers are not read
+        assert_eq!(
+            ReadPlan::new(registers, [(5, 1), (11, 1), (30, 20)], 123, 4).blocks(),
+            [(0, 4), (8, 3), (12, 4), (40, 1)]
+        );
+        assert_eq!(
+            ReadPlan::new(registers, [(4, 0), (11, 1)], 123, 4).blocks(),
+            [(0, 11), (12, 4), (40, 1)]
+        );
+
+        let words = RegisterWords(vec![(0, vec![1, 2, 3]), (10, vec![4, 5])]);
+        assert_eq!(words.get(1, 2), Some(&[2, 3][..]));
+        assert_eq!(words.get(11, 1), Some(&[5][..]));
+        assert_eq!(words.get(2, 2), None);
+        assert_eq!(words.get(5, 1), None);
+    }
+
+    #[cfg(feature = "simulator")]
+    #[tokio::test]
+    async fn test_adaptive_read_plan() {
+        use crate::simulator::testing::replay_simulator;
+        use crate::simulator::LocalClient;
+        use tokio_modbus::{client::Context, slave::Slave, Request, Response};
+
+        // Registers 2 and 3 are not implemented by the device
+        let device = replay_simulator([
+            (
+                1,
+                Request::ReadInputRegisters(0, 2),
+                Response::ReadInputRegisters(vec![1, 2]),
+            ),
+            (
+                1,
+                Request::ReadInputRegisters(4, 3),
+                Response::ReadInputRegisters(vec![5, 6, 7]),
+            ),
+        ]);
+        let local = LocalClient::new(device, Slave(1));
+        let mut client = Context::from(local.clone());
+        let mut plan = ReadPlan::new([(0, 2), (2, 1), (3, 1), (4, 2), (6, 1)], [], 123, 0);
+        assert_eq!(
+            plan.read_input_registers(&mut client).await.unwrap(),
+            Err(Exception::IllegalDataAddress)
+        );
+
+        let mut plan = plan.with_adaptive(true);
+        let words = plan
+            .read_input_registers(&mut client)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(words.get(0, 2), Some(&[1, 2][..]));
+        assert_eq!(words.get(4, 3), Some(&[5, 6, 7][..]));
+        assert_eq!(words.get(2, 1), None);
+        assert_eq!(plan.unreadable(), [(2, 1), (3, 1)]);
+        assert_eq!(plan.blocks(), [(0, 2), (4, 3)]);
+
+        // The learned plan reads the readable fields only
+        local.take_requests();
+        plan.read_input_registers(&mut client)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(local.requests().len(), 2);
+    }
+
+    #[cfg(feature = "simulator")]
+    #[tokio::test]
+    async fn test_adaptive_read_plan_gap() {
+        use crate::simulator::testing::replay_simulator;
+        use crate::simulator::LocalClient;
+        use tokio_modbus::{client::Context, slave::Slave, Request, Response};
+
+        // Register 2 between the fields is not implemented by the device
+        let device = replay_simulator([
+            (
+                1,
+                Request::ReadInputRegisters(0, 2),
+                Response::ReadInputRegisters(vec![1, 2]),
+            ),
+            (
+                1,
+                Request::ReadInputRegisters(3, 2),
+                Response::ReadInputRegisters(vec![4, 5]),
+            ),
+        ]);
+        let local = LocalClient::new(device, Slave(1));
+        let mut client = Context::from(local.clone());
+        let mut plan = ReadPlan::new([(0, 2), (3, 1), (4, 1)], [], 123, 1).with_adaptive(true);
+        assert_eq!(plan.blocks(), [(0, 5)]);
+
+        let words = plan
+            .read_input_registers(&mut client)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(words.get(0, 2), Some(&[1, 2][..]));
+        assert_eq!(words.get(3, 1), Some(&[4][..]));
+        assert_eq!(words.get(4, 1), Some(&[5][..]));
+        assert!(plan.unreadable().is_empty());
+        // The fields after the gap are still read together
+        assert_eq!(plan.blocks(), [(0, 2), (3, 2)]);
+
+        local.take_requests();
+        plan.read_input_registers(&mut client)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(local.requests().len(), 2);
+    }
+}
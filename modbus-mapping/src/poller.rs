@@ -0,0 +1,284 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    marker::PhantomData,
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
+use tokio::{
+    sync::watch,
+    task::JoinHandle,
+    time::{timeout, MissedTickBehavior},
+};
+use tokio_modbus::client::Reader;
+
+use crate::core::{HoldingRegisterMap, InputRegisterMap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Quality of [Snapshot] value.
+pub enum Quality {
+    /// No poll has finished yet; the value is the default value.
+    NotPolled,
+    /// The value was read by the latest poll.
+    Good,
+    /// The latest poll missed its deadline; the value was read by an earlier poll.
+    Stale,
+    /// The latest poll failed; the value was read by an earlier poll.
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Value published by [Poller].
+pub struct Snapshot<T> {
+    /// The last value read successfully, or the default value before the first successful poll.
+    pub value: T,
+    /// Time of the last successful poll.
+    pub timestamp: Option<SystemTime>,
+    pub quality: Quality,
+    /// Error of the latest poll if failed.
+    pub error: Option<String>,
+}
+
+/// Register map type read by the poll.
+#[async_trait]
+trait Read<T>: Send + Sync + 'static {
+    async fn read(&self, value: &mut T, client: &mut dyn Reader) -> tokio_modbus::Result<()>;
+}
+
+struct InputRegisters;
+
+#[async_trait]
+impl<T: InputRegisterMap + Send> Read<T> for InputRegisters {
+    async fn read(&self, value: &mut T, client: &mut dyn Reader) -> tokio_modbus::Result<()> {
+        value.update_from_input_registers(client).await
+    }
+}
+
+struct HoldingRegisters;
+
+#[async_trait]
+impl<T: HoldingRegisterMap + Send> Read<T> for HoldingRegisters {
+    async fn read(&self, value: &mut T, client: &mut dyn Reader) -> tokio_modbus::Result<()> {
+        value.update_from_holding_registers(client).await
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Periodic polling of a register map type publishing [Snapshot]s through [tokio::sync::watch] channel.
+///
+/// Each poll waits a random delay up to jitter after the interval tick to spread the load of several pollers.
+/// A poll not finished within its deadline is cancelled and the snapshot is marked as stale.
+/// The missed ticks are skipped.
+pub struct Poller<C> {
+    client: C,
+    interval: Duration,
+    jitter: Duration,
+    deadline: Option<Duration>,
+}
+
+impl<C: Reader + 'static> Poller<C> {
+    /// Poll by `client` every `interval`.
+    pub fn new(client: C, interval: Duration) -> Self {
+        Self {
+            client,
+            interval,
+            jitter: Duration::ZERO,
+            deadline: None,
+        }
+    }
+
+    /// Delay each poll by uniformly distributed random delay up to `jitter`.
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Set the deadline of each poll; the interval by default.
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Poll input registers mapped to `T` in a background task.
+    pub fn spawn_input_registers<T>(self) -> PollerHandle<T>
+    where
+        T: InputRegisterMap + Clone + Send + Sync + 'static,
+    {
+        self.spawn(InputRegisters)
+    }
+
+    /// Poll holding registers mapped to `T` in a background task.
+    pub fn spawn_holding_registers<T>(self) -> PollerHandle<T>
+    where
+        T: HoldingRegisterMap + Clone + Send + Sync + 'static,
+    {
+        self.spawn(HoldingRegisters)
+    }
+
+    fn spawn<T, R>(mut self, reader: R) -> PollerHandle<T>
+    where
+        T: Default + Clone + Send + Sync + 'static,
+        R: Read<T>,
+    {
+        let (sender, receiver) = watch::channel(Snapshot {
+            value: T::default(),
+            timestamp: None,
+            quality: Quality::NotPolled,
+            error: None,
+        });
+        let deadline = self.deadline.unwrap_or(self.interval);
+
+        let task = tokio::spawn(async move {
+            let mut rng = JitterRng::new();
+            let mut interval = tokio::time::interval(self.interval);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                if !self.jitter.is_zero() {
+                    tokio::time::sleep(self.jitter.mul_f64(rng.next_fraction())).await;
+                }
+
+                // Read into a copy so a partially failed poll does not corrupt the snapshot value
+                let mut value = sender.borrow().value.clone();
+                let result = timeout(deadline, reader.read(&mut value, &mut self.client)).await;
+                sender.send_modify(|snapshot| match result {
+                    Ok(Ok(Ok(()))) => {
+                        snapshot.value = value;
+                        snapshot.timestamp = Some(SystemTime::now());
+                        snapshot.quality = Quality::Good;
+                        snapshot.error = None;
+                    }
+                    Ok(Ok(Err(exc))) => {
+                        snapshot.quality = Quality::Error;
+                        snapshot.error = Some(exc.to_string());
+                    }
+                    Ok(Err(err)) => {
+                        snapshot.quality = Quality::Error;
+                        snapshot.error = Some(err.to_string());
+                    }
+                    Err(_elapsed) => {
+                        snapshot.quality = Quality::Stale;
+                        snapshot.error = Some("Deadline exceeded".to_string());
+                    }
+                });
+            }
+        });
+
+        PollerHandle {
+            receiver,
+            task,
+            value: PhantomData,
+        }
+    }
+}
+
+/// SplitMix64 generator of the poll delays, randomly seeded by each poller.
+struct JitterRng(u64);
+
+impl JitterRng {
+    fn new() -> Self {
+        Self(RandomState::new().build_hasher().finish())
+    }
+
+    /// Random number in `[0, 1)` range.
+    fn next_fraction(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[derive(Debug)]
+/// Handle to the poller spawned by [Poller]. Dropping the handle stops the polling.
+pub struct PollerHandle<T> {
+    receiver: watch::Receiver<Snapshot<T>>,
+    task: JoinHandle<()>,
+    value: PhantomData<T>,
+}
+
+impl<T: Clone> PollerHandle<T> {
+    /// Receiver of the published snapshots.
+    pub fn subscribe(&self) -> watch::Receiver<Snapshot<T>> {
+        self.receiver.clone()
+    }
+
+    /// The latest published snapshot.
+    pub fn latest(&self) -> Snapshot<T> {
+        self.receiver.borrow().clone()
+    }
+}
+
+impl<T> Drop for PollerHandle<T> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(all(test, feature = "simulator"))]
+mod tests {
+    use super::*;
+    use crate::simulator::testing::{local_context, replay_simulator};
+    use crate::simulator::{FaultySimulation, ReplayDevice, Simulator};
+    use tokio_modbus::{Exception, Request, Response};
+
+    #[derive(Debug, Clone, Default, PartialEq)]
+    struct Power(u16);
+
+    #[async_trait]
+    impl InputRegisterMap for Power {
+        async fn update_from_input_registers(
+            &mut self,
+            client: &mut dyn Reader,
+        ) -> tokio_modbus::Result<()> {
+            match client.read_input_registers(0, 1).await? {
+                Ok(words) => {
+                    self.0 = words[0];
+                    Ok(Ok(()))
+                }
+                Err(exc) => Ok(Err(exc)),
+            }
+        }
+    }
+
+    fn simulation() -> FaultySimulation<Simulator<ReplayDevice>> {
+        FaultySimulation::new(replay_simulator([(
+            1,
+            Request::ReadInputRegisters(0, 1),
+            Response::ReadInputRegisters(vec![42]),
+        )]))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_poller() {
+        let poller = Poller::new(local_context(simulation(), 1), Duration::from_secs(1))
+            .with_jitter(Duration::from_millis(100))
+            .spawn_input_registers::<Power>();
+        let mut receiver = poller.subscribe();
+        assert_eq!(receiver.borrow().quality, Quality::NotPolled);
+        receiver.changed().await.unwrap();
+        let snapshot = receiver.borrow_and_update().clone();
+        assert_eq!(snapshot.value, Power(42));
+        assert_eq!(snapshot.quality, Quality::Good);
+        assert!(snapshot.timestamp.is_some());
+
+        let faulty = simulation().with_address_exception(0..=0, Exception::IllegalDataAddress);
+        let poller = Poller::new(local_context(faulty, 1), Duration::from_secs(1))
+            .spawn_input_registers::<Power>();
+        let mut receiver = poller.subscribe();
+        receiver.changed().await.unwrap();
+        assert_eq!(receiver.borrow().quality, Quality::Error);
+        assert_eq!(receiver.borrow().value, Power(0));
+
+        let faulty = simulation().with_latency(Duration::from_secs(2), Duration::ZERO);
+        let poller = Poller::new(local_context(faulty, 1), Duration::from_secs(1))
+            .with_deadline(Duration::from_millis(500))
+            .spawn_input_registers::<Power>();
+        let mut receiver = poller.subscribe();
+        receiver.changed().await.unwrap();
+        assert_eq!(receiver.borrow().quality, Quality::Stale);
+    }
+}
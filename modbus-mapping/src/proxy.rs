@@ -0,0 +1,482 @@
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use futures::future::{self, BoxFuture, FutureExt};
+use tokio::{
+    net::TcpListener,
+    sync::watch,
+    task::JoinHandle,
+    time::{timeout, Instant, MissedTickBehavior},
+};
+use tokio_modbus::{client::Reader, prelude::SlaveRequest, server, Exception, Request, Response};
+
+use crate::core::{HoldingRegisterMap, InputRegisterMap};
+use crate::simulator::{
+    transport::{self, Framing},
+    HoldingRegisterModel, InputRegisterModel, Registers, Reply, Simulation,
+};
+
+/// Default timeout of the polls and forwarded writes.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Default)]
+/// Registers served by the proxy.
+struct Cache {
+    input_registers: Registers,
+    holding_registers: Registers,
+    /// At least one poll succeeded.
+    polled: bool,
+}
+
+/// Register map type polled by the proxy.
+#[async_trait]
+trait Map: Send {
+    async fn read(&mut self, client: &mut dyn Reader) -> tokio_modbus::Result<()>;
+
+    fn update_registers(&self, cache: &mut Cache) -> Result<(), Exception>;
+}
+
+struct InputMap<T>(T);
+
+#[async_trait]
+impl<T: InputRegisterMap + InputRegisterModel + Send> Map for InputMap<T> {
+    async fn read(&mut self, client: &mut dyn Reader) -> tokio_modbus::Result<()> {
+        self.0.update_from_input_registers(client).await
+    }
+
+    fn update_registers(&self, cache: &mut Cache) -> Result<(), Exception> {
+        InputRegisterModel::update_registers(&self.0, &mut cache.input_registers)
+    }
+}
+
+struct HoldingMap<T>(T);
+
+#[async_trait]
+impl<T: HoldingRegisterMap + HoldingRegisterModel + Send> Map for HoldingMap<T> {
+    async fn read(&mut self, client: &mut dyn Reader) -> tokio_modbus::Result<()> {
+        self.0.update_from_holding_registers(client).await
+    }
+
+    fn update_registers(&self, cache: &mut Cache) -> Result<(), Exception> {
+        HoldingRegisterModel::update_registers(&self.0, &mut cache.holding_registers)
+    }
+}
+
+/// State shared by the polling task and the connections.
+struct Shared<C> {
+    client: tokio::sync::Mutex<C>,
+    cache: Mutex<Cache>,
+    timeout: Duration,
+    write_lock: Option<Duration>,
+    /// Client IP holding the write lock and its expiration.
+    lock_owner: Mutex<Option<(IpAddr, Instant)>>,
+    /// Time of the last successful poll.
+    last_poll: watch::Sender<Option<Instant>>,
+}
+
+/// Caching proxy polling a device through register map types and serving the latest values to any number of Modbus TCP clients.
+///
+/// The read requests of input and holding registers are answered from the cache, regardless of their unit id.
+/// Until the first successful poll, they are answered with [Exception::GatewayTargetDevice].
+/// A failed poll leaves the cached values unchanged.
+/// The holding register writes are forwarded to the device, the other requests are answered with [Exception::IllegalFunction].
+///
+/// With write lock, the client IP writing successfully first holds the lock until it does not write for the lock duration;
+/// the writes of the other client IPs are answered with [Exception::ServerDeviceBusy] meanwhile.
+pub struct Proxy<C> {
+    client: C,
+    interval: Duration,
+    timeout: Duration,
+    write_lock: Option<Duration>,
+    maps: Vec<Box<dyn Map>>,
+    cache: Cache,
+}
+
+impl<C: Reader + 'static> Proxy<C> {
+    /// Poll the device by `client` every `interval`.
+    pub fn new(client: C, interval: Duration) -> Self {
+        Self {
+            client,
+            interval,
+            timeout: DEFAULT_TIMEOUT,
+            write_lock: None,
+            maps: Vec::new(),
+            cache: Cache::default(),
+        }
+    }
+
+    /// Poll input registers mapped to `T`.
+    pub fn with_input_registers<T>(mut self) -> Self
+    where
+        T: InputRegisterMap + InputRegisterModel + Default + Send + 'static,
+    {
+        let value = T::default();
+        self.cache
+            .input_registers
+            .extend(InputRegisterModel::new_registers(&value));
+        self.maps.push(Box::new(InputMap(value)));
+        self
+    }
+
+    /// Poll holding registers mapped to `T`.
+    pub fn with_holding_registers<T>(mut self) -> Self
+    where
+        T: HoldingRegisterMap + HoldingRegisterModel + Default + Send + 'static,
+    {
+        let value = T::default();
+        self.cache
+            .holding_registers
+            .extend(HoldingRegisterModel::new_registers(&value));
+        self.maps.push(Box::new(HoldingMap(value)));
+        self
+    }
+
+    /// Set timeout of the polls and forwarded writes.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Lock the writes to the client IP writing first until it does not write for `duration`.
+    pub fn with_write_lock(mut self, duration: Duration) -> Self {
+        self.write_lock = Some(duration);
+        self
+    }
+
+    /// Bind the socket and run the proxy in a background task.
+    pub async fn spawn(self, socket_addr: SocketAddr) -> io::Result<ProxyHandle> {
+        let listener = TcpListener::bind(socket_addr).await?;
+        let local_addr = listener.local_addr()?;
+        let shared = Arc::new(Shared {
+            client: tokio::sync::Mutex::new(self.client),
+            cache: Mutex::new(self.cache),
+            timeout: self.timeout,
+            write_lock: self.write_lock,
+            lock_owner: Mutex::new(None),
+            last_poll: watch::Sender::new(None),
+        });
+        let last_poll = shared.last_poll.subscribe();
+
+        let poll = Self::poll(shared.clone(), self.maps, self.interval);
+        let task = tokio::spawn(async move {
+            tokio::select! {
+                _ = poll => Ok(()),
+                served = Self::serve(listener, shared) => served,
+            }
+        });
+
+        Ok(ProxyHandle {
+            local_addr,
+            last_poll,
+            task,
+        })
+    }
+
+    async fn poll(shared: Arc<Shared<C>>, mut maps: Vec<Box<dyn Map>>, interval: Duration) {
+        let mut interval = tokio::time::interval(interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            let poll = async {
+                let mut client = shared.client.lock().await;
+                for map in maps.iter_mut() {
+                    if !matches!(map.read(&mut *client).await, Ok(Ok(()))) {
+                        return;
+                    }
+                }
+                // Updated before releasing the client, so a forwarded write is not overwritten by older values
+                let mut cache = shared.cache.lock().unwrap();
+                if maps
+                    .iter()
+                    .all(|map| map.update_registers(&mut cache).is_ok())
+                {
+                    cache.polled = true;
+                    shared.last_poll.send_replace(Some(Instant::now()));
+                }
+            };
+            // The cached values are kept after a failed poll
+            let _ = timeout(shared.timeout, poll).await;
+        }
+    }
+
+    /// Serve the connections until the listener fails, the connections are ended when the future is dropped.
+    async fn serve(listener: TcpListener, shared: Arc<Shared<C>>) -> io::Result<()> {
+        let (_closed_tx, closed_rx) = watch::channel(());
+        let on_connected = |stream, peer: SocketAddr| {
+            let connection = Connection {
+                shared: shared.clone(),
+                peer: peer.ip(),
+            };
+            let closed_rx = closed_rx.clone();
+            async move {
+                Ok(Some(transport::connection(
+                    connection,
+                    stream,
+                    closed_rx,
+                    Framing::Mbap,
+                )))
+            }
+        };
+        server::tcp::Server::new(listener)
+            .serve(&on_connected, transport::ignore_process_error)
+            .await
+    }
+}
+
+impl<C> std::fmt::Debug for Proxy<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Proxy")
+            .field("interval", &self.interval)
+            .field("timeout", &self.timeout)
+            .field("write_lock", &self.write_lock)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Connection of a proxy client serving its requests.
+struct Connection<C> {
+    shared: Arc<Shared<C>>,
+    peer: IpAddr,
+}
+
+impl<C> Clone for Connection<C> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            peer: self.peer,
+        }
+    }
+}
+
+impl<C: Reader + 'static> Connection<C> {
+    fn read(&self, request: &Request) -> Result<Response, Exception> {
+        let cache = self.shared.cache.lock().unwrap();
+        if !cache.polled {
+            return Err(Exception::GatewayTargetDevice);
+        }
+        match *request {
+            Request::ReadInputRegisters(addr, cnt) => Ok(Response::ReadInputRegisters(
+                cache.input_registers.read(addr, cnt)?,
+            )),
+            Request::ReadHoldingRegisters(addr, cnt) => Ok(Response::ReadHoldingRegisters(
+                cache.holding_registers.read(addr, cnt)?,
+            )),
+            _ => Err(Exception::IllegalFunction),
+        }
+    }
+
+    /// Whether another client IP holds the write lock.
+    fn locked_out(&self, lock_owner: &Option<(IpAddr, Instant)>) -> bool {
+        matches!(*lock_owner, Some((owner, expiration)) if owner != self.peer && Instant::now() < expiration)
+    }
+
+    /// Take or renew the write lock after a successful write, unless another client IP took it meanwhile.
+    fn lock(&self) {
+        let Some(duration) = self.shared.write_lock else {
+            return;
+        };
+        let mut lock_owner = self.shared.lock_owner.lock().unwrap();
+        if !self.locked_out(&lock_owner) {
+            *lock_owner = Some((self.peer, Instant::now() + duration));
+        }
+    }
+
+    async fn write(self, request: Request<'static>) -> Result<Response, Exception> {
+        if self.locked_out(&self.shared.lock_owner.lock().unwrap()) {
+            return Err(Exception::ServerDeviceBusy);
+        }
+        let (addr, words) = match &request {
+            Request::WriteSingleRegister(addr, word) => (*addr, vec![*word]),
+            Request::WriteMultipleRegisters(addr, words) => (*addr, words.to_vec()),
+            _ => unreachable!("Only holding register writes are forwarded"),
+        };
+
+        let mut client = self.shared.client.lock().await;
+        let response = match timeout(self.shared.timeout, client.call(request)).await {
+            Ok(Ok(result)) => result?,
+            Ok(Err(_)) | Err(_) => return Err(Exception::GatewayTargetDevice),
+        };
+        self.lock();
+        // The written values are served until the next poll
+        let _ = self
+            .shared
+            .cache
+            .lock()
+            .unwrap()
+            .holding_registers
+            .write(addr, &words);
+
+        Ok(response)
+    }
+}
+
+impl<C: Reader + 'static> Simulation for Connection<C> {
+    fn call(&self, req: SlaveRequest<'static>) -> BoxFuture<'static, Reply> {
+        match req.request {
+            Request::WriteSingleRegister(..) | Request::WriteMultipleRegisters(..) => {
+                self.clone().write(req.request).map(Reply::from).boxed()
+            }
+            request => future::ready(self.read(&request).into()).boxed(),
+        }
+    }
+
+    /// The state is updated by the polling task.
+    fn update_state(&self, _elapsed: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(future::ready(()))
+    }
+}
+
+#[derive(Debug)]
+/// Handle to the proxy spawned by [Proxy::spawn]. Dropping the handle stops the proxy.
+pub struct ProxyHandle {
+    local_addr: SocketAddr,
+    last_poll: watch::Receiver<Option<Instant>>,
+    task: JoinHandle<io::Result<()>>,
+}
+
+impl ProxyHandle {
+    /// Local address of the proxy socket, e.g. to find out the port bound to port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Receiver notified about the time of each successful poll, e.g. to wait for the first one.
+    pub fn subscribe(&self) -> watch::Receiver<Option<Instant>> {
+        self.last_poll.clone()
+    }
+}
+
+impl Drop for ProxyHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::WordOrder;
+    use crate::frame;
+    use crate::simulator::testing::{local_context, replay_simulator};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpSocket, TcpStream},
+    };
+    use tokio_modbus::client::Writer;
+
+    #[derive(Debug, Default)]
+    struct Setpoint {
+        value: u16,
+    }
+
+    #[async_trait]
+    impl HoldingRegisterMap for Setpoint {
+        async fn update_from_holding_registers(
+            &mut self,
+            client: &mut dyn Reader,
+        ) -> tokio_modbus::Result<()> {
+            let words = match client.read_holding_registers(0, 1).await? {
+                Ok(words) => words,
+                Err(exc) => return Ok(Err(exc)),
+            };
+            self.value = words[0];
+            Ok(Ok(()))
+        }
+
+        async fn write_to_registers(&self, client: &mut dyn Writer) -> tokio_modbus::Result<()> {
+            client.write_single_register(0, self.value).await
+        }
+    }
+
+    impl HoldingRegisterModel for Setpoint {
+        fn new_registers(&self) -> Registers {
+            let mut registers = Registers::default();
+            registers.insert(0, vec![0]);
+            registers
+        }
+
+        fn update_registers(&self, registers: &mut Registers) -> Result<(), Exception> {
+            registers.set(0, self.value, WordOrder::BigEndian)
+        }
+
+        fn update_self(&mut self, registers: &Registers) -> Result<(), Exception> {
+            self.value = registers.get(0, WordOrder::BigEndian)?;
+            Ok(())
+        }
+    }
+
+    /// Send request `pdu` and return the response PDU.
+    async fn call(stream: &mut TcpStream, pdu: &[u8]) -> Vec<u8> {
+        stream
+            .write_all(&frame::encode_mbap_frame(1, 1, pdu))
+            .await
+            .unwrap();
+        let mut header = [0; frame::MBAP_HEADER_LEN];
+        stream.read_exact(&mut header).await.unwrap();
+        let (_, _, pdu_len) = frame::decode_mbap_header(&header).unwrap();
+        let mut pdu = vec![0; pdu_len];
+        stream.read_exact(&mut pdu).await.unwrap();
+        pdu
+    }
+
+    #[tokio::test]
+    async fn test_proxy() {
+        let device = replay_simulator([(
+            1,
+            Request::ReadHoldingRegisters(0, 1),
+            Response::ReadHoldingRegisters(vec![42]),
+        )]);
+        let client = local_context(device.clone(), 1);
+        let proxy = Proxy::new(client, Duration::from_millis(10))
+            .with_holding_registers::<Setpoint>()
+            .with_write_lock(Duration::from_secs(10))
+            .spawn("127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+        proxy.subscribe().wait_for(Option::is_some).await.unwrap();
+
+        let mut first = TcpStream::connect(proxy.local_addr()).await.unwrap();
+        let socket = TcpSocket::new_v4().unwrap();
+        socket.bind("127.0.0.2:0".parse().unwrap()).unwrap();
+        let mut second = socket.connect(proxy.local_addr()).await.unwrap();
+
+        let read = [0x03, 0x00, 0x00, 0x00, 0x01];
+        assert_eq!(call(&mut first, &read).await, [0x03, 0x02, 0x00, 42]);
+        assert_eq!(call(&mut second, &read).await, [0x03, 0x02, 0x00, 42]);
+        // Input registers are not proxied
+        assert_eq!(
+            call(&mut first, &[0x04, 0x00, 0x00, 0x00, 0x01]).await,
+            [0x84, Exception::IllegalDataAddress as u8]
+        );
+
+        // The failed write does not take the write lock
+        assert_eq!(
+            call(&mut second, &[0x06, 0x00, 0x01, 0x00, 0x07]).await,
+            [0x86, Exception::IllegalDataAddress as u8]
+        );
+
+        // The first client writing successfully takes the write lock
+        let write = [0x06, 0x00, 0x00, 0x00, 0x07];
+        assert_eq!(call(&mut first, &write).await, write);
+        assert_eq!(
+            call(&mut second, &[0x06, 0x00, 0x00, 0x00, 0x08]).await,
+            [0x86, Exception::ServerDeviceBusy as u8]
+        );
+        assert_eq!(call(&mut second, &read).await, [0x03, 0x02, 0x00, 7]);
+
+        // The write was forwarded to the device
+        let req = SlaveRequest {
+            slave: 1,
+            request: Request::ReadHoldingRegisters(0, 1),
+        };
+        assert_eq!(
+            Simulation::call(&device, req).await,
+            Reply::Response(Ok(Response::ReadHoldingRegisters(vec![7])))
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use std::{
+    fmt::{self, Debug},
+    io::{self, BufRead},
+    str::FromStr,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    time::Instant,
+};
+use tokio_modbus::{
+    client::{Client, Context},
+    slave::{Slave, SlaveContext, SlaveId},
+    Exception, Request, Response,
+};
+
+use crate::frame;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Single request/response exchange captured by [Recorder].
+///
+/// The record is stored as a single tab-separated line
+/// `<elapsed seconds>\t<slave id>\t<request PDU hex>\t<response PDU hex>`, e.g.
+/// `1.250000\t1\t0400000002\t040400640032`.
+pub struct Record {
+    /// Time since the recording start.
+    pub elapsed: Duration,
+    pub slave: SlaveId,
+    pub request: Request<'static>,
+    pub response: Result<Response, Exception>,
+}
+
+impl Record {
+    /// Encode the record as a line without the trailing newline.
+    ///
+    /// Fails if the request values do not fit in a PDU.
+    pub fn to_line(&self) -> io::Result<String> {
+        let fc = self.request.function_code().value();
+        Ok(format!(
+            "{:.6}\t{}\t{}\t{}",
+            self.elapsed.as_secs_f64(),
+            self.slave,
+            to_hex(&frame::encode_request(&self.request)?),
+            to_hex(&frame::encode_response(fc, &self.response)),
+        ))
+    }
+}
+
+impl FromStr for Record {
+    type Err = io::Error;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            |msg: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{msg}: {line}"));
+        let [elapsed, slave, request, response] =
+            line.split('\t')
+                .collect::<Vec<_>>()
+                .try_into()
+                .map_err(|_| invalid("Invalid number of record fields"))?;
+
+        let elapsed = elapsed
+            .parse::<f64>()
+            .ok()
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            .ok_or_else(|| invalid("Invalid record time"))?;
+        let slave = slave.parse().map_err(|_| invalid("Invalid slave id"))?;
+        let request = from_hex(request)
+            .and_then(|pdu| frame::decode_request(&pdu).ok())
+            .ok_or_else(|| invalid("Invalid request PDU"))?;
+        let response = from_hex(response).ok_or_else(|| invalid("Invalid response PDU"))?;
+        let response = frame::decode_response(&request, &response)?;
+
+        Ok(Self {
+            elapsed,
+            slave,
+            request,
+            response,
+        })
+    }
+}
+
+/// Read records written by [Recorder], one per line. Empty lines are skipped.
+pub fn read_records(reader: impl BufRead) -> io::Result<Vec<Record>> {
+    reader
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|line| line?.trim_end().parse())
+        .collect()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Client wrapper capturing the request/response exchanges of the wrapped client into the asynchronous `writer`,
+/// e.g. [`tokio::io::BufWriter`] of [`tokio::fs::File`].
+///
+/// Convert it into [Context] to use it with [`crate::core::InputRegisterMap`] and [`crate::core::HoldingRegisterMap`]
+/// to record a real device session, e.g. to be replayed later by `simulator::ReplayDevice`.
+/// Requests failing with a transport error are not recorded.
+pub struct Recorder<C, W> {
+    client: C,
+    slave: Slave,
+    writer: W,
+    start: Instant,
+}
+
+impl<C: Client, W: AsyncWrite + Unpin + Send> Recorder<C, W> {
+    /// Record the exchanges of `client` connected to `slave` into `writer`.
+    pub fn new(mut client: C, slave: Slave, writer: W) -> Self {
+        client.set_slave(slave);
+        Self {
+            client,
+            slave,
+            writer,
+            start: Instant::now(),
+        }
+    }
+
+    /// Flush the writer and return the wrapped client and writer.
+    pub async fn into_inner(mut self) -> io::Result<(C, W)> {
+        self.writer.flush().await?;
+        Ok((self.client, self.writer))
+    }
+}
+
+impl<C, W> Debug for Recorder<C, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recorder")
+            .field("slave", &self.slave)
+            .field("start", &self.start)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: Client, W> SlaveContext for Recorder<C, W> {
+    fn set_slave(&mut self, slave: Slave) {
+        self.slave = slave;
+        self.client.set_slave(slave);
+    }
+}
+
+#[async_trait]
+impl<C: Client, W: AsyncWrite + Unpin + Send> Client for Recorder<C, W> {
+    async fn call(&mut self, request: Request<'_>) -> tokio_modbus::Result<Response> {
+        if let Request::Disconnect = request {
+            self.writer.flush().await?;
+            return self.client.call(request).await;
+        }
+        let request = request.into_owned();
+        let response = self.client.call(request.clone()).await?;
+        let record = Record {
+            elapsed: self.start.elapsed(),
+            slave: self.slave.into(),
+            request,
+            response: response.clone(),
+        };
+        let mut line = record.to_line()?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes()).await?;
+
+        Ok(response)
+    }
+}
+
+impl<C, W> From<Recorder<C, W>> for Context
+where
+    C: Client + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    fn from(recorder: Recorder<C, W>) -> Self {
+        Context::from(Box::new(recorder) as Box<dyn Client>)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_record_line() {
+        let record = Record {
+            elapsed: Duration::from_millis(1250),
+            slave: 1,
+            request: Request::ReadInputRegisters(0, 2),
+            response: Ok(Response::ReadInputRegisters(vec![100, 50])),
+        };
+        let line = record.to_line().unwrap();
+        assert_eq!(line, "1.250000\t1\t0400000002\t040400640032");
+        assert_eq!(line.parse::<Record>().unwrap(), record);
+
+        let record = Record {
+            elapsed: Duration::ZERO,
+            slave: 2,
+            request: Request::WriteMultipleRegisters(10, Cow::Owned(vec![1, 2])),
+            response: Err(Exception::IllegalDataAddress),
+        };
+        let line = record.to_line().unwrap();
+        let records = read_records(format!("{line}\n\n{line}\n").as_bytes()).unwrap();
+        assert_eq!(records, vec![record.clone(), record]);
+
+        assert!("1.0\t1\t04000000\t0402".parse::<Record>().is_err());
+
+        let record = Record {
+            elapsed: Duration::ZERO,
+            slave: 1,
+            request: Request::WriteMultipleRegisters(0, Cow::Owned(vec![0; 128])),
+            response: Err(Exception::IllegalDataValue),
+        };
+        assert!(record.to_line().is_err());
+    }
+
+    #[cfg(feature = "simulator")]
+    #[tokio::test]
+    async fn test_recorder() {
+        use crate::simulator::{testing::replay_simulator, LocalClient};
+
+        let device = replay_simulator([(
+            1,
+            Request::ReadInputRegisters(0, 2),
+            Response::ReadInputRegisters(vec![100, 50]),
+        )]);
+        let local = LocalClient::new(device, Slave(2));
+        let mut recorder = Recorder::new(local.clone(), Slave(1), Vec::new());
+        recorder
+            .call(Request::ReadInputRegisters(0, 2))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(local.requests()[0].slave, 1);
+
+        let (_, writer) = recorder.into_inner().await.unwrap();
+        let records = read_records(&writer[..]).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].slave, 1);
+        assert_eq!(
+            records[0].response,
+            Ok(Response::ReadInputRegisters(vec![100, 50]))
+        );
+    }
+}
@@ -1,64 +1,177 @@
 use std::{
-    collections::HashMap,
-    fmt::Debug,
-    marker::PhantomData,
-    net::SocketAddr,
-    sync::{Arc, Mutex},
+    collections::HashMap, fmt::Debug, future::Future, io, marker::PhantomData, net::SocketAddr,
+    ops::Range, sync::Arc, time::Duration,
 };
+mod async_device;
+mod fault;
+mod local;
+mod model;
+mod replay;
+mod script;
+#[cfg(test)]
+pub(crate) mod testing;
+pub(crate) mod transport;
+
+pub use async_device::{AsyncDevice, AsyncSimulator};
+pub use fault::FaultySimulation;
+pub use local::LocalClient;
+pub use model::ModelDevice;
+pub use replay::ReplayDevice;
+pub use script::{Generator, Script, ScriptedDevice};
+use transport::{Framing, TcpOptions};
+
 use tokio_stream::wrappers::IntervalStream;
 use tokio_stream::StreamExt;
 
-use crate::codec::Word;
-use futures::future;
-use tokio::net::TcpListener;
+use crate::codec::{Coil, Decode, Encode, Word, WordOrder};
+use crate::identification::{
+    DeviceIdentification, ENCAPSULATED_INTERFACE_TRANSPORT, READ_DEVICE_IDENTIFICATION,
+};
+use bytes::Bytes;
+use futures::future::{self, BoxFuture, FutureExt};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::sync::{oneshot, Mutex};
 use tokio::task::JoinHandle;
-use tokio_modbus::{server, Address, Exception, Quantity, Request, Response};
+use tokio_modbus::{
+    prelude::SlaveRequest,
+    server,
+    slave::{Slave, SlaveId},
+    Address, Exception, Quantity, Request, Response,
+};
+
+#[derive(Debug, Clone, Default)]
+/// Consecutive values stored in dense segments sorted by their start address.
+struct Segments<T>(Vec<(Address, Vec<T>)>);
+
+impl<T: Copy + Default> Segments<T> {
+    /// Insert new consecutive `values` starting at `addr` address.
+    /// Values beyond the address space are dropped.
+    fn insert(&mut self, addr: Address, mut values: Vec<T>) {
+        let start = addr as usize;
+        values.truncate(Address::MAX as usize + 1 - start);
+        if values.is_empty() {
+            return;
+        }
+        let end = start + values.len();
+
+        // Segments overlapping or adjacent to the inserted ones are merged
+        let first = self
+            .0
+            .partition_point(|(seg_addr, seg)| *seg_addr as usize + seg.len() < start);
+        let last = self
+            .0
+            .partition_point(|(seg_addr, _)| *seg_addr as usize <= end);
+        if first == last {
+            self.0.insert(first, (addr, values));
+            return;
+        }
+
+        let merged_start = start.min(self.0[first].0 as usize);
+        let (last_addr, last_seg) = &self.0[last - 1];
+        let merged_end = end.max(*last_addr as usize + last_seg.len());
+        let mut merged = vec![T::default(); merged_end - merged_start];
+        for (seg_addr, seg) in self.0.drain(first..last) {
+            let offset = seg_addr as usize - merged_start;
+            merged[offset..offset + seg.len()].copy_from_slice(&seg);
+        }
+        let offset = start - merged_start;
+        merged[offset..offset + values.len()].copy_from_slice(&values);
+        self.0.insert(first, (merged_start as Address, merged));
+    }
+
+    fn shrink(&mut self) {
+        for (_, seg) in self.0.iter_mut() {
+            seg.shrink_to_fit();
+        }
+        self.0.shrink_to_fit()
+    }
+
+    /// Index of the segment and offset within it of `cnt` consecutive values starting at `addr`.
+    fn find(&self, addr: Address, cnt: usize) -> Option<(usize, usize)> {
+        let i = self
+            .0
+            .partition_point(|(seg_addr, _)| *seg_addr <= addr)
+            .checked_sub(1)?;
+        let (seg_addr, seg) = &self.0[i];
+        let offset = (addr - seg_addr) as usize;
+
+        (offset + cnt <= seg.len()).then_some((i, offset))
+    }
+
+    fn read(&self, addr: Address, cnt: Quantity) -> Result<Vec<T>, Exception> {
+        if cnt == 0 {
+            return Ok(vec![]);
+        }
+        let (i, offset) = self
+            .find(addr, cnt.into())
+            .ok_or(Exception::IllegalDataAddress)?;
+
+        Ok(self.0[i].1[offset..offset + cnt as usize].to_vec())
+    }
+
+    fn write(&mut self, addr: Address, values: &[T]) -> Result<(), Exception> {
+        if values.is_empty() {
+            return Ok(());
+        }
+        let (i, offset) = self
+            .find(addr, values.len())
+            .ok_or(Exception::IllegalDataAddress)?;
+        self.0[i].1[offset..offset + values.len()].copy_from_slice(values);
+
+        Ok(())
+    }
+}
 
 #[derive(Debug, Clone, Default)]
 /// A raw Modbus input and holding registers representation
-pub struct Registers(HashMap<Address, Word>);
+///
+/// Consecutive registers are stored in dense segments sorted by their start address.
+pub struct Registers(Segments<Word>);
 
 impl Registers {
     /// Insert new consecutive registers with `words` values starting at `addr` address.
     pub fn insert(&mut self, addr: Address, words: Vec<Word>) {
-        for (i, value) in words.into_iter().enumerate() {
-            let reg_addr = addr + i as Address;
-            self.0.insert(reg_addr, value);
+        self.0.insert(addr, words)
+    }
+
+    /// Insert all registers of `other`, e.g. of another register map type.
+    pub fn extend(&mut self, other: Registers) {
+        for (addr, words) in other.0 .0 {
+            self.insert(addr, words);
         }
     }
 
     /// Helper method to shrink the container size.
     pub fn shrink(&mut self) {
-        self.0.shrink_to_fit()
+        self.0.shrink()
     }
 
     /// Read `cnt` consecutive registers starting at `addr`.
     pub fn read(&self, addr: Address, cnt: Quantity) -> Result<Vec<Word>, Exception> {
-        let mut response_values = vec![0; cnt.into()];
-        for i in 0..cnt {
-            let reg_addr = addr + i;
-            if let Some(r) = self.0.get(&reg_addr) {
-                response_values[i as usize] = *r;
-            } else {
-                return Err(Exception::IllegalDataAddress);
-            }
-        }
-
-        Ok(response_values)
+        self.0.read(addr, cnt)
     }
 
     /// Write `words` into existing consecutive registers starting at `addr`.
     pub fn write(&mut self, addr: Address, words: &[Word]) -> Result<(), Exception> {
-        for (i, value) in words.iter().enumerate() {
-            let reg_addr = addr + i as Address;
-            if let Some(r) = self.0.get_mut(&reg_addr) {
-                *r = *value;
-            } else {
-                return Err(Exception::IllegalDataAddress);
-            }
-        }
+        self.0.write(addr, words)
+    }
 
-        Ok(())
+    /// Read and decode value of type `T` stored at `addr` in `order`.
+    pub fn get<T: Decode>(&self, addr: Address, order: WordOrder) -> Result<T, Exception> {
+        let cnt = std::mem::size_of::<T>().div_ceil(2) as Quantity;
+        order
+            .decode(&self.read(addr, cnt)?)
+            .map_err(|_| Exception::IllegalDataValue)
+    }
+
+    /// Encode `value` in `order` and write it into existing registers at `addr`.
+    pub fn set<T: Encode>(
+        &mut self,
+        addr: Address,
+        value: T,
+        order: WordOrder,
+    ) -> Result<(), Exception> {
+        self.write(addr, &order.encode(value))
     }
 }
 
@@ -68,6 +181,11 @@ pub trait InputRegisterModel {
     /// Create new input register map.
     fn new_registers(&self) -> Registers;
     fn update_registers(&self, registers: &mut Registers) -> Result<(), Exception>;
+
+    /// Set the actual (scaled) `value` of the field `name`. Return `false` if there is no such field.
+    fn set_field(&mut self, _name: &str, _value: f64) -> bool {
+        false
+    }
 }
 
 /// Trait with complementary functionality to [`crate::core::HoldingRegisterMap`]
@@ -76,15 +194,131 @@ pub trait HoldingRegisterModel {
     fn new_registers(&self) -> Registers;
     fn update_registers(&self, registers: &mut Registers) -> Result<(), Exception>;
     fn update_self(&mut self, registers: &Registers) -> Result<(), Exception>;
+
+    /// Names and actual (scaled) values decoded from `registers` of the fields stored within `addrs` range.
+    fn field_values(
+        &self,
+        _registers: &Registers,
+        _addrs: Range<Address>,
+    ) -> Result<Vec<(&'static str, f64)>, Exception> {
+        Ok(vec![])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Holding register field write passed to the hook of [DataStore::service_call_with_hook].
+pub struct FieldWrite {
+    pub name: &'static str,
+    /// Actual value before the write.
+    pub old: f64,
+    /// Actual value written.
+    pub new: f64,
+}
+
+/// No model; the registers are maintained directly in [DataStore].
+impl InputRegisterModel for () {
+    fn new_registers(&self) -> Registers {
+        Registers::default()
+    }
+
+    fn update_registers(&self, _registers: &mut Registers) -> Result<(), Exception> {
+        Ok(())
+    }
+}
+
+/// No model; the registers are maintained directly in [DataStore].
+impl HoldingRegisterModel for () {
+    fn new_registers(&self) -> Registers {
+        Registers::default()
+    }
+
+    fn update_registers(&self, _registers: &mut Registers) -> Result<(), Exception> {
+        Ok(())
+    }
+
+    fn update_self(&mut self, _registers: &Registers) -> Result<(), Exception> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// A raw Modbus coils and discrete inputs representation
+///
+/// Consecutive coils are stored in dense segments sorted by their start address.
+pub struct Coils(Segments<Coil>);
+
+impl Coils {
+    /// Insert new consecutive coils with `bits` values starting at `addr` address.
+    /// Coils beyond the address space are dropped.
+    pub fn insert(&mut self, addr: Address, bits: Vec<Coil>) {
+        self.0.insert(addr, bits)
+    }
+
+    /// Read `cnt` consecutive coils starting at `addr`.
+    pub fn read(&self, addr: Address, cnt: Quantity) -> Result<Vec<Coil>, Exception> {
+        self.0.read(addr, cnt)
+    }
+
+    /// Write `bits` into existing consecutive coils starting at `addr`.
+    /// Nothing is written unless all the coils exist.
+    pub fn write(&mut self, addr: Address, bits: &[Coil]) -> Result<(), Exception> {
+        self.0.write(addr, bits)
+    }
+}
+
+/// Function code of Report Server ID request.
+pub(crate) const REPORT_SERVER_ID: u8 = 0x11;
+
+#[derive(Debug, Clone, Default)]
+/// Data served by Report Server ID (FC17) request
+pub struct ServerId {
+    pub id: u8,
+    pub run_indicator_status: bool,
+    pub additional_data: Vec<u8>,
+}
+
+impl ServerId {
+    /// Encode data of Report Server ID response: byte count, server id, run indicator status and additional data.
+    fn encode(&self) -> Result<Vec<u8>, Exception> {
+        let byte_count = u8::try_from(self.additional_data.len() + 2)
+            .map_err(|_| Exception::ServerDeviceFailure)?;
+        let mut data = vec![
+            byte_count,
+            self.id,
+            if self.run_indicator_status {
+                0xFF
+            } else {
+                0x00
+            },
+        ];
+        data.extend_from_slice(&self.additional_data);
+        Ok(data)
+    }
+
+    /// Decode data of Report Server ID response.
+    pub(crate) fn decode(data: &[u8]) -> Option<Self> {
+        match data {
+            [_byte_count, id, run_indicator_status, additional_data @ ..] => Some(Self {
+                id: *id,
+                run_indicator_status: *run_indicator_status == 0xFF,
+                additional_data: additional_data.to_vec(),
+            }),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 /// Modbus data store to be used in IO operations for the simulation purposes.
 pub struct DataStore<I, H> {
+    pub coils: Coils,
+    pub discrete_inputs: Coils,
     pub input_registers: Registers,
     input_register_model: PhantomData<I>,
     pub holding_registers: Registers,
     holding_register_model: PhantomData<H>,
+    pub device_identification: DeviceIdentification,
+    pub server_id: ServerId,
 }
 
 impl<I, H> Default for DataStore<I, H>
@@ -94,10 +328,14 @@ where
 {
     fn default() -> Self {
         Self {
+            coils: Coils::default(),
+            discrete_inputs: Coils::default(),
             input_registers: I::default().new_registers(),
             input_register_model: PhantomData,
             holding_registers: H::default().new_registers(),
             holding_register_model: PhantomData,
+            device_identification: DeviceIdentification::default(),
+            server_id: ServerId::default(),
         }
     }
 }
@@ -107,48 +345,147 @@ where
     I: InputRegisterModel,
     H: HoldingRegisterModel,
 {
+    /// Create data store with the registers of `input_register_model` and `holding_register_model`.
+    pub fn new(input_register_model: &I, holding_register_model: &H) -> Self {
+        Self {
+            coils: Coils::default(),
+            discrete_inputs: Coils::default(),
+            input_registers: input_register_model.new_registers(),
+            input_register_model: PhantomData,
+            holding_registers: holding_register_model.new_registers(),
+            holding_register_model: PhantomData,
+            device_identification: DeviceIdentification::default(),
+            server_id: ServerId::default(),
+        }
+    }
+
     /// Method to be used to implement [tokio_modbus::server::Service](https://docs.rs/tokio-modbus/latest/tokio_modbus/server/trait.Service.html).
     pub fn service_call(
         &mut self,
         holding_register_model: &mut H,
         req: Request,
     ) -> future::Ready<Result<Response, Exception>> {
+        future::ready(self.call(holding_register_model, req, &mut |_| Ok(())))
+    }
+
+    /// Same as [DataStore::service_call] but `on_write` is called for each holding register field touched by a write request
+    /// before the write is done, e.g. to trigger side effects.
+    /// The write request is rejected (and no register is changed) if `on_write` returns an exception.
+    pub fn service_call_with_hook(
+        &mut self,
+        holding_register_model: &mut H,
+        req: Request,
+        mut on_write: impl FnMut(&FieldWrite) -> Result<(), Exception>,
+    ) -> future::Ready<Result<Response, Exception>> {
+        future::ready(self.call(holding_register_model, req, &mut on_write))
+    }
+
+    /// Write `words` into holding registers starting at `addr` and update the model.
+    ///
+    /// The registers and the model are restored if the hook or the model update fails.
+    fn write_holding_registers(
+        &mut self,
+        holding_register_model: &mut H,
+        addr: Address,
+        words: &[Word],
+        on_write: &mut dyn FnMut(&FieldWrite) -> Result<(), Exception>,
+    ) -> Result<(), Exception> {
+        let addrs = addr..addr.saturating_add(words.len() as Address);
+        let old_words = self.holding_registers.read(addr, words.len() as Quantity)?;
+        let old_values =
+            holding_register_model.field_values(&self.holding_registers, addrs.clone())?;
+        self.holding_registers.write(addr, words)?;
+
+        let hook_result =
+            holding_register_model
+                .field_values(&self.holding_registers, addrs)
+                .and_then(|new_values| {
+                    old_values.into_iter().zip(new_values).try_for_each(
+                        |((name, old), (_, new))| on_write(&FieldWrite { name, old, new }),
+                    )
+                });
+        let result =
+            hook_result.and_then(|()| holding_register_model.update_self(&self.holding_registers));
+        if let Err(exception) = result {
+            self.holding_registers.write(addr, &old_words)?;
+            // The failed update may have set some fields already
+            holding_register_model.update_self(&self.holding_registers)?;
+            return Err(exception);
+        }
+
+        Ok(())
+    }
+
+    fn call(
+        &mut self,
+        holding_register_model: &mut H,
+        req: Request,
+        on_write: &mut dyn FnMut(&FieldWrite) -> Result<(), Exception>,
+    ) -> Result<Response, Exception> {
         match req {
-            Request::ReadInputRegisters(addr, cnt) => match self.input_registers.read(addr, cnt) {
-                Ok(values) => future::ready(Ok(Response::ReadInputRegisters(values))),
-                Err(err) => future::ready(Err(err)),
-            },
-            Request::ReadHoldingRegisters(addr, cnt) => {
-                match self.holding_registers.read(addr, cnt) {
-                    Ok(values) => future::ready(Ok(Response::ReadHoldingRegisters(values))),
-                    Err(err) => future::ready(Err(err)),
-                }
+            Request::ReadCoils(addr, cnt) => Ok(Response::ReadCoils(self.coils.read(addr, cnt)?)),
+            Request::ReadDiscreteInputs(addr, cnt) => Ok(Response::ReadDiscreteInputs(
+                self.discrete_inputs.read(addr, cnt)?,
+            )),
+            Request::WriteSingleCoil(addr, value) => {
+                self.coils.write(addr, std::slice::from_ref(&value))?;
+                Ok(Response::WriteSingleCoil(addr, value))
+            }
+            Request::WriteMultipleCoils(addr, values) => {
+                self.coils.write(addr, &values)?;
+                Ok(Response::WriteMultipleCoils(addr, values.len() as u16))
             }
+            Request::ReadInputRegisters(addr, cnt) => Ok(Response::ReadInputRegisters(
+                self.input_registers.read(addr, cnt)?,
+            )),
+            Request::ReadHoldingRegisters(addr, cnt) => Ok(Response::ReadHoldingRegisters(
+                self.holding_registers.read(addr, cnt)?,
+            )),
             Request::WriteMultipleRegisters(addr, values) => {
-                match self.holding_registers.write(addr, &values) {
-                    Ok(_) => match holding_register_model.update_self(&self.holding_registers) {
-                        Ok(_) => future::ready(Ok(Response::WriteMultipleRegisters(
-                            addr,
-                            values.len() as u16,
-                        ))),
-                        Err(err) => future::ready(Err(err)),
-                    },
-                    Err(err) => future::ready(Err(err)),
-                }
+                self.write_holding_registers(holding_register_model, addr, &values, on_write)?;
+                Ok(Response::WriteMultipleRegisters(addr, values.len() as u16))
             }
             Request::WriteSingleRegister(addr, value) => {
-                match self
-                    .holding_registers
-                    .write(addr, std::slice::from_ref(&value))
-                {
-                    Ok(_) => match holding_register_model.update_self(&self.holding_registers) {
-                        Ok(_) => future::ready(Ok(Response::WriteSingleRegister(addr, value))),
-                        Err(err) => future::ready(Err(err)),
-                    },
-                    Err(err) => future::ready(Err(err)),
-                }
+                self.write_holding_registers(holding_register_model, addr, &[value], on_write)?;
+                Ok(Response::WriteSingleRegister(addr, value))
+            }
+            Request::MaskWriteRegister(addr, and_mask, or_mask) => {
+                let current = self.holding_registers.read(addr, 1)?[0];
+                let value = (current & and_mask) | (or_mask & !and_mask);
+                self.write_holding_registers(holding_register_model, addr, &[value], on_write)?;
+                Ok(Response::MaskWriteRegister(addr, and_mask, or_mask))
             }
-            _ => future::ready(Err(Exception::IllegalFunction)),
+            Request::ReadWriteMultipleRegisters(read_addr, cnt, write_addr, values) => {
+                // The write operation is performed before the read, so the read range is validated first
+                self.holding_registers.read(read_addr, cnt)?;
+                self.write_holding_registers(
+                    holding_register_model,
+                    write_addr,
+                    &values,
+                    on_write,
+                )?;
+                Ok(Response::ReadWriteMultipleRegisters(
+                    self.holding_registers.read(read_addr, cnt)?,
+                ))
+            }
+            Request::Custom(REPORT_SERVER_ID, _) => Ok(Response::Custom(
+                REPORT_SERVER_ID,
+                Bytes::from(self.server_id.encode()?),
+            )),
+            Request::Custom(ENCAPSULATED_INTERFACE_TRANSPORT, data) => match data.as_ref() {
+                [READ_DEVICE_IDENTIFICATION, read_device_id_code, object_id] => {
+                    let data = self
+                        .device_identification
+                        .encode_response(*read_device_id_code, *object_id)?;
+                    Ok(Response::Custom(
+                        ENCAPSULATED_INTERFACE_TRANSPORT,
+                        Bytes::from(data),
+                    ))
+                }
+                [READ_DEVICE_IDENTIFICATION, ..] => Err(Exception::IllegalDataValue),
+                _ => Err(Exception::IllegalFunction),
+            },
+            _ => Err(Exception::IllegalFunction),
         }
     }
 }
@@ -161,11 +498,14 @@ pub trait Device {
 
     fn service_call(&mut self, req: Request) -> future::Ready<Result<Response, Exception>>;
 
-    fn update_state(&mut self);
+    /// Update the device state by `elapsed` time since the previous update.
+    fn update_state(&mut self, elapsed: Duration);
 }
 
 #[derive(Debug, Clone)]
 /// Wrapper around [Device] needed because of [tokio_modbus::server::Service](https://docs.rs/tokio-modbus/latest/tokio_modbus/server/trait.Service.html).
+///
+/// The device is guarded by asynchronous mutex, so waiting for it does not block the runtime.
 pub struct Simulator<D: Device>(pub Arc<Mutex<D>>);
 
 impl<D: Device> Simulator<D> {
@@ -174,83 +514,879 @@ impl<D: Device> Simulator<D> {
     }
 }
 
-impl<D: Device> tokio_modbus::server::Service for Simulator<D> {
+impl<D: Device + Send + 'static> tokio_modbus::server::Service for Simulator<D> {
     type Request = Request<'static>;
-    type Future = future::Ready<Result<Response, Exception>>;
+    type Future = BoxFuture<'static, Result<Response, Exception>>;
 
     fn call(&self, req: Self::Request) -> Self::Future {
-        let device = &mut self.0.lock().unwrap();
-        device.service_call(req)
+        let device = self.0.clone();
+        async move { device.lock().await.service_call(req).await }.boxed()
     }
 }
 
-/// Utility function to run TCP simulator forever.
-pub async fn run_tcp_simulator<D: Device + Clone + Sync + Send + 'static>(
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Reply of [Simulation] to a request.
+pub enum Reply {
+    /// Send the response (or exception) back to the client.
+    Response(Result<Response, Exception>),
+    /// Leave the request unanswered.
+    NoResponse,
+    /// Send the response (or exception) in a corrupted frame rejected by the client, i.e. with invalid MBAP protocol identifier.
+    CorruptedResponse(Result<Response, Exception>),
+}
+
+impl From<Result<Response, Exception>> for Reply {
+    fn from(value: Result<Response, Exception>) -> Self {
+        Reply::Response(value)
+    }
+}
+
+/// Modbus simulation to be run by [run_tcp_simulator], [run_udp_simulator] or [run_rtu_simulator], e.g. [Simulator] or [MultiSimulator].
+pub trait Simulation: Clone + Send + Sync + 'static {
+    /// Serve the request addressed to `req.slave`.
+    fn call(&self, req: SlaveRequest<'static>) -> BoxFuture<'static, Reply>;
+
+    /// Update the state of the simulated device(s) by `elapsed` time since the previous update.
+    ///
+    /// The simulators measure the time by tokio timer, so it can be controlled
+    /// by `tokio::time::pause` and `tokio::time::advance` in tests.
+    fn update_state(&self, elapsed: Duration) -> BoxFuture<'static, ()>;
+}
+
+impl<D: Device + Clone + Send + 'static> Simulation for Simulator<D> {
+    fn call(&self, req: SlaveRequest<'static>) -> BoxFuture<'static, Reply> {
+        server::Service::call(self, req.request)
+            .map(Reply::from)
+            .boxed()
+    }
+
+    fn update_state(&self, elapsed: Duration) -> BoxFuture<'static, ()> {
+        let device = self.0.clone();
+        async move { device.lock().await.update_state(elapsed) }.boxed()
+    }
+}
+
+/// Object safe counterpart of [Simulation] to store different simulations together.
+trait DynSimulation: Send + Sync {
+    fn call(&self, req: SlaveRequest<'static>) -> BoxFuture<'static, Reply>;
+
+    fn update_state(&self, elapsed: Duration) -> BoxFuture<'static, ()>;
+}
+
+impl<S: Simulation> DynSimulation for S {
+    fn call(&self, req: SlaveRequest<'static>) -> BoxFuture<'static, Reply> {
+        Simulation::call(self, req)
+    }
+
+    fn update_state(&self, elapsed: Duration) -> BoxFuture<'static, ()> {
+        Simulation::update_state(self, elapsed)
+    }
+}
+
+/// Slave id of the broadcast requests.
+const BROADCAST_SLAVE_ID: SlaveId = 0;
+
+#[derive(Clone)]
+/// Simulation of several slaves (e.g. behind a gateway or on a RS-485 bus) routing the requests by slave id.
+///
+/// Requests to unknown slave ids are answered with [Exception::GatewayTargetDevice] by default.
+/// Broadcast requests (slave id 0) are served by all slaves and left unanswered.
+pub struct MultiSimulator {
+    slaves: HashMap<SlaveId, Arc<dyn DynSimulation>>,
+    unknown_slave_exception: Option<Exception>,
+}
+
+impl Debug for MultiSimulator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut slave_ids = self.slaves.keys().collect::<Vec<_>>();
+        slave_ids.sort();
+        f.debug_struct("MultiSimulator")
+            .field("slaves", &slave_ids)
+            .field("unknown_slave_exception", &self.unknown_slave_exception)
+            .finish()
+    }
+}
+
+impl Default for MultiSimulator {
+    fn default() -> Self {
+        Self {
+            slaves: HashMap::new(),
+            unknown_slave_exception: Some(Exception::GatewayTargetDevice),
+        }
+    }
+}
+
+impl MultiSimulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve requests addressed to `slave` by `simulation`. The previous simulation of the same slave is replaced.
+    pub fn with_slave(mut self, slave: Slave, simulation: impl Simulation) -> Self {
+        self.slaves.insert(slave.into(), Arc::new(simulation));
+        self
+    }
+
+    /// Set the exception returned for requests addressed to unknown slaves.
+    pub fn with_unknown_slave_exception(mut self, exception: Exception) -> Self {
+        self.unknown_slave_exception = Some(exception);
+        self
+    }
+
+    /// Leave requests addressed to unknown slaves unanswered like a RS-485 bus does.
+    pub fn without_unknown_slave_response(mut self) -> Self {
+        self.unknown_slave_exception = None;
+        self
+    }
+}
+
+impl Simulation for MultiSimulator {
+    fn call(&self, req: SlaveRequest<'static>) -> BoxFuture<'static, Reply> {
+        if req.slave == BROADCAST_SLAVE_ID {
+            let calls = self
+                .slaves
+                .values()
+                .map(|simulation| simulation.call(req.clone()));
+            return Box::pin(future::join_all(calls).map(|_| Reply::NoResponse));
+        }
+        match (self.slaves.get(&req.slave), self.unknown_slave_exception) {
+            (Some(simulation), _) => simulation.call(req),
+            (None, Some(exception)) => Box::pin(future::ready(Reply::Response(Err(exception)))),
+            (None, None) => Box::pin(future::ready(Reply::NoResponse)),
+        }
+    }
+
+    fn update_state(&self, elapsed: Duration) -> BoxFuture<'static, ()> {
+        let updates = self
+            .slaves
+            .values()
+            .map(|simulation| simulation.update_state(elapsed));
+        Box::pin(future::join_all(updates).map(|_| ()))
+    }
+}
+
+/// Default period of [Simulation::update_state] calls.
+const DEFAULT_STATE_UPDATE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Fail with [io::ErrorKind::InvalidInput] error if the state update period is zero
+/// or the time scale is negative or not finite.
+fn check_state_updates(state_update_period: Duration, time_scale: f64) -> io::Result<()> {
+    if state_update_period.is_zero() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "State update period must be non-zero",
+        ));
+    }
+    if !time_scale.is_finite() || time_scale < 0.0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Time scale must be finite and non-negative",
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+/// Handle to the simulator spawned by [TcpSimulator::spawn], [UdpSimulator::spawn] or [RtuSimulator::spawn].
+///
+/// Dropping the handle shuts the simulator down.
+pub struct SimulatorHandle {
+    local_addr: Option<SocketAddr>,
+    shutdown_tx: oneshot::Sender<()>,
+    task: JoinHandle<io::Result<()>>,
+}
+
+impl SimulatorHandle {
+    /// Socket address the TCP or UDP simulator is bound to (useful when bound to port 0).
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Wait until the simulator stops because of an error.
+    pub async fn join(self) -> io::Result<()> {
+        // Keep the shutdown sender alive, dropping it stops the simulator
+        let Self {
+            shutdown_tx, task, ..
+        } = self;
+        let result = task.await.map_err(io::Error::other)?;
+        drop(shutdown_tx);
+        result
+    }
+
+    /// Stop the simulator and wait until it finishes.
+    pub async fn shutdown(self) -> io::Result<()> {
+        let _ = self.shutdown_tx.send(());
+        self.task.await.map_err(io::Error::other)?
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Builder of Modbus TCP simulator.
+pub struct TcpSimulator<S> {
     socket_addr: SocketAddr,
-    simulator: Simulator<D>,
-    state_update_period: std::time::Duration,
-) {
-    let simulator_clone = simulator.clone();
-    let server_task = tokio::spawn(async move {
-        let _ = run_tcp_server_context(socket_addr, simulator_clone).await;
-    });
+    simulation: S,
+    state_update_period: Duration,
+    time_scale: f64,
+    options: TcpOptions,
+}
+
+impl<S: Simulation> TcpSimulator<S> {
+    /// Create TCP simulator of `simulation` to be bound to `socket_addr`. Use port 0 to bind to any free port.
+    pub fn new(socket_addr: SocketAddr, simulation: S) -> Self {
+        Self {
+            socket_addr,
+            simulation,
+            state_update_period: DEFAULT_STATE_UPDATE_PERIOD,
+            time_scale: 1.0,
+            options: TcpOptions::default(),
+        }
+    }
+
+    /// Serve RTU frames instead of MBAP frames over the TCP connections (RTU over TCP), e.g. as a serial to TCP converter.
+    /// The responses to broadcast requests (slave id 0) are discarded then.
+    pub fn with_rtu_framing(mut self, rtu_framing: bool) -> Self {
+        self.options.framing = if rtu_framing {
+            Framing::Rtu
+        } else {
+            Framing::Mbap
+        };
+        self
+    }
 
-    let state_update_task = spawn_state_update_task(simulator, state_update_period);
+    /// Secure the TCP connections by TLS (Modbus/TCP Security), see [crate::tls::server_config].
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, config: Arc<tokio_rustls::rustls::ServerConfig>) -> Self {
+        self.options.tls = Some(config);
+        self
+    }
+
+    /// Set non-zero period of the state updates.
+    pub fn with_state_update_period(mut self, state_update_period: Duration) -> Self {
+        self.state_update_period = state_update_period;
+        self
+    }
+
+    /// Scale the elapsed time passed to the state updates by finite non-negative `time_scale`,
+    /// e.g. `10.0` to run the simulation 10 times faster than real time.
+    pub fn with_time_scale(mut self, time_scale: f64) -> Self {
+        self.time_scale = time_scale;
+        self
+    }
 
-    let _ = state_update_task.await;
-    let _ = server_task.await;
+    /// Bind the socket and run the simulator in a background task.
+    pub async fn spawn(self) -> io::Result<SimulatorHandle> {
+        check_state_updates(self.state_update_period, self.time_scale)?;
+        let listener = TcpListener::bind(self.socket_addr).await?;
+        let local_addr = listener.local_addr()?;
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let task = tokio::spawn(async move {
+            let server = transport::serve_tcp(listener, self.simulation.clone(), self.options);
+            run_until(
+                server,
+                self.simulation,
+                self.state_update_period,
+                self.time_scale,
+                shutdown_rx,
+            )
+            .await
+        });
+
+        Ok(SimulatorHandle {
+            local_addr: Some(local_addr),
+            shutdown_tx,
+            task,
+        })
+    }
+
+    /// Bind the socket and run the simulator until `shutdown` completes.
+    pub async fn run_until(self, shutdown: impl Future<Output = ()>) -> io::Result<()> {
+        check_state_updates(self.state_update_period, self.time_scale)?;
+        let listener = TcpListener::bind(self.socket_addr).await?;
+        let server = transport::serve_tcp(listener, self.simulation.clone(), self.options);
+        run_until(
+            server,
+            self.simulation,
+            self.state_update_period,
+            self.time_scale,
+            shutdown,
+        )
+        .await
+    }
 }
 
-/// Utility function to spawn and run simulator RTU simulator forever.
-pub async fn run_rtu_simulator<D: Device + Clone + Sync + Send + 'static>(
-    path: &str,
+#[derive(Debug, Clone)]
+/// Builder of Modbus UDP simulator.
+pub struct UdpSimulator<S> {
+    socket_addr: SocketAddr,
+    simulation: S,
+    state_update_period: Duration,
+    time_scale: f64,
+}
+
+impl<S: Simulation> UdpSimulator<S> {
+    /// Create UDP simulator of `simulation` to be bound to `socket_addr`. Use port 0 to bind to any free port.
+    pub fn new(socket_addr: SocketAddr, simulation: S) -> Self {
+        Self {
+            socket_addr,
+            simulation,
+            state_update_period: DEFAULT_STATE_UPDATE_PERIOD,
+            time_scale: 1.0,
+        }
+    }
+
+    /// Set non-zero period of the state updates.
+    pub fn with_state_update_period(mut self, state_update_period: Duration) -> Self {
+        self.state_update_period = state_update_period;
+        self
+    }
+
+    /// Scale the elapsed time passed to the state updates by finite non-negative `time_scale`,
+    /// e.g. `10.0` to run the simulation 10 times faster than real time.
+    pub fn with_time_scale(mut self, time_scale: f64) -> Self {
+        self.time_scale = time_scale;
+        self
+    }
+
+    /// Bind the socket and run the simulator in a background task.
+    pub async fn spawn(self) -> io::Result<SimulatorHandle> {
+        check_state_updates(self.state_update_period, self.time_scale)?;
+        let socket = UdpSocket::bind(self.socket_addr).await?;
+        let local_addr = socket.local_addr()?;
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let task = tokio::spawn(async move {
+            run_until(
+                transport::serve_udp(socket, self.simulation.clone()),
+                self.simulation,
+                self.state_update_period,
+                self.time_scale,
+                shutdown_rx,
+            )
+            .await
+        });
+
+        Ok(SimulatorHandle {
+            local_addr: Some(local_addr),
+            shutdown_tx,
+            task,
+        })
+    }
+
+    /// Bind the socket and run the simulator until `shutdown` completes.
+    pub async fn run_until(self, shutdown: impl Future<Output = ()>) -> io::Result<()> {
+        check_state_updates(self.state_update_period, self.time_scale)?;
+        let socket = UdpSocket::bind(self.socket_addr).await?;
+        run_until(
+            transport::serve_udp(socket, self.simulation.clone()),
+            self.simulation,
+            self.state_update_period,
+            self.time_scale,
+            shutdown,
+        )
+        .await
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Builder of Modbus RTU simulator.
+///
+/// The tokio-modbus RTU server answers every request, so [Reply::NoResponse] and [Reply::CorruptedResponse]
+/// are answered with [Exception::GatewayTargetDevice] instead.
+pub struct RtuSimulator<S> {
+    path: String,
     baud_rate: u32,
-    simulator: Simulator<D>,
-    state_update_period: std::time::Duration,
-) {
-    let builder = tokio_serial::new(path, baud_rate);
-    let serial_stream = tokio_serial::SerialStream::open(&builder).unwrap();
-    let server = server::rtu::Server::new(serial_stream);
-    let service = simulator.clone();
-
-    let server_task = tokio::spawn(async move {
-        if let Err(err) = server.serve_forever(service).await {
-            eprintln!("{err}");
-        };
-    });
+    simulation: S,
+    state_update_period: Duration,
+    time_scale: f64,
+}
 
-    let state_update_task = spawn_state_update_task(simulator, state_update_period);
+impl<S: Simulation> RtuSimulator<S> {
+    /// Create RTU simulator of `simulation` to be served on `path` serial port.
+    pub fn new(path: &str, baud_rate: u32, simulation: S) -> Self {
+        Self {
+            path: path.to_string(),
+            baud_rate,
+            simulation,
+            state_update_period: DEFAULT_STATE_UPDATE_PERIOD,
+            time_scale: 1.0,
+        }
+    }
+
+    /// Set non-zero period of the state updates.
+    pub fn with_state_update_period(mut self, state_update_period: Duration) -> Self {
+        self.state_update_period = state_update_period;
+        self
+    }
+
+    /// Scale the elapsed time passed to the state updates by finite non-negative `time_scale`,
+    /// e.g. `10.0` to run the simulation 10 times faster than real time.
+    pub fn with_time_scale(mut self, time_scale: f64) -> Self {
+        self.time_scale = time_scale;
+        self
+    }
+
+    /// Open the serial port and run the simulator in a background task.
+    pub async fn spawn(self) -> io::Result<SimulatorHandle> {
+        let serial_stream = self.open()?;
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let task = tokio::spawn(async move {
+            run_until(
+                transport::serve_rtu(serial_stream, self.simulation.clone()),
+                self.simulation,
+                self.state_update_period,
+                self.time_scale,
+                shutdown_rx,
+            )
+            .await
+        });
+
+        Ok(SimulatorHandle {
+            local_addr: None,
+            shutdown_tx,
+            task,
+        })
+    }
 
-    let _ = state_update_task.await;
-    let _ = server_task.await;
+    /// Open the serial port and run the simulator until `shutdown` completes.
+    pub async fn run_until(self, shutdown: impl Future<Output = ()>) -> io::Result<()> {
+        let serial_stream = self.open()?;
+        run_until(
+            transport::serve_rtu(serial_stream, self.simulation.clone()),
+            self.simulation,
+            self.state_update_period,
+            self.time_scale,
+            shutdown,
+        )
+        .await
+    }
+
+    fn open(&self) -> io::Result<tokio_serial::SerialStream> {
+        check_state_updates(self.state_update_period, self.time_scale)?;
+        if self.baud_rate == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Baud rate must be non-zero",
+            ));
+        }
+        let builder = tokio_serial::new(&self.path, self.baud_rate);
+        Ok(tokio_serial::SerialStream::open(&builder)?)
+    }
+}
+
+/// Utility function to run TCP simulator forever.
+pub async fn run_tcp_simulator<S: Simulation>(
+    socket_addr: SocketAddr,
+    simulator: S,
+    state_update_period: Duration,
+) -> io::Result<()> {
+    TcpSimulator::new(socket_addr, simulator)
+        .with_state_update_period(state_update_period)
+        .run_until(future::pending())
+        .await
 }
 
-async fn run_tcp_server_context<D: Device + Clone + Sync + Send + 'static>(
+/// Utility function to run RTU over TCP simulator forever.
+pub async fn run_rtu_over_tcp_simulator<S: Simulation>(
     socket_addr: SocketAddr,
-    simulator: Simulator<D>,
-) {
-    let listener = TcpListener::bind(socket_addr).await.unwrap();
-    let server = server::tcp::Server::new(listener);
-    let new_service = |_socket_addr| Ok(Some(simulator.clone()));
-    let on_connected = |stream, socket_addr| async move {
-        server::tcp::accept_tcp_connection(stream, socket_addr, new_service)
-    };
-    let on_process_error = |err| {
-        eprintln!("{err}");
+    simulator: S,
+    state_update_period: Duration,
+) -> io::Result<()> {
+    TcpSimulator::new(socket_addr, simulator)
+        .with_rtu_framing(true)
+        .with_state_update_period(state_update_period)
+        .run_until(future::pending())
+        .await
+}
+
+/// Utility function to run Modbus/TCP Security simulator forever.
+///
+/// The server certificate chain and private key are read from PEM files `cert_path` and `key_path`.
+/// The client certificates are verified against the CA certificates read from `client_ca_path`,
+/// or not requested if it is `None`, see [crate::tls::server_config_without_client_auth].
+#[cfg(feature = "tls")]
+pub async fn run_tls_simulator<S: Simulation>(
+    socket_addr: SocketAddr,
+    simulator: S,
+    state_update_period: Duration,
+    cert_path: &str,
+    key_path: &str,
+    client_ca_path: Option<&str>,
+) -> io::Result<()> {
+    let certs = crate::tls::load_certs(cert_path)?;
+    let key = crate::tls::load_private_key(key_path)?;
+    let config = match client_ca_path {
+        Some(path) => crate::tls::server_config(certs, key, crate::tls::load_certs(path)?)?,
+        None => crate::tls::server_config_without_client_auth(certs, key)?,
     };
-    server.serve(&on_connected, on_process_error).await.unwrap();
+    TcpSimulator::new(socket_addr, simulator)
+        .with_tls(config)
+        .with_state_update_period(state_update_period)
+        .run_until(future::pending())
+        .await
+}
+
+/// Utility function to run UDP simulator forever.
+pub async fn run_udp_simulator<S: Simulation>(
+    socket_addr: SocketAddr,
+    simulator: S,
+    state_update_period: Duration,
+) -> io::Result<()> {
+    UdpSimulator::new(socket_addr, simulator)
+        .with_state_update_period(state_update_period)
+        .run_until(future::pending())
+        .await
 }
 
-fn spawn_state_update_task<D: Device + Clone + Sync + Send + 'static>(
-    simulator: Simulator<D>,
-    state_update_period: std::time::Duration,
-) -> JoinHandle<()> {
+/// Utility function to run RTU simulator forever.
+pub async fn run_rtu_simulator<S: Simulation>(
+    path: &str,
+    baud_rate: u32,
+    simulator: S,
+    state_update_period: Duration,
+) -> io::Result<()> {
+    RtuSimulator::new(path, baud_rate, simulator)
+        .with_state_update_period(state_update_period)
+        .run_until(future::pending())
+        .await
+}
+
+/// Run `server` and the state updates of `simulator` until the server fails or `shutdown` completes.
+async fn run_until<S: Simulation, F>(
+    server: impl Future<Output = io::Result<()>>,
+    simulator: S,
+    state_update_period: Duration,
+    time_scale: f64,
+    shutdown: F,
+) -> io::Result<()>
+where
+    F: Future,
+{
     let interval = tokio::time::interval(state_update_period);
     let mut stream = IntervalStream::new(interval);
+    let state_update = async move {
+        let mut previous = None;
+        while let Some(instant) = stream.next().await {
+            let elapsed = previous.map_or(Duration::ZERO, |previous| instant - previous);
+            previous = Some(instant);
+            simulator.update_state(elapsed.mul_f64(time_scale)).await;
+        }
+    };
+
+    tokio::select! {
+        result = server => result,
+        _ = state_update => Ok(()),
+        _ = shutdown => Ok(()),
+    }
+}
 
-    tokio::spawn(async move {
-        while let Some(_instant) = stream.next().await {
-            simulator.0.lock().unwrap().update_state();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    #[derive(Debug, Clone, Default)]
+    struct Model;
+
+    impl InputRegisterModel for Model {
+        fn new_registers(&self) -> Registers {
+            Registers::default()
         }
-    })
+
+        fn update_registers(&self, _registers: &mut Registers) -> Result<(), Exception> {
+            Ok(())
+        }
+    }
+
+    impl HoldingRegisterModel for Model {
+        fn new_registers(&self) -> Registers {
+            let mut registers = Registers::default();
+            registers.insert(0, vec![1, 2, 3]);
+            registers
+        }
+
+        fn update_registers(&self, _registers: &mut Registers) -> Result<(), Exception> {
+            Ok(())
+        }
+
+        fn update_self(&mut self, _registers: &Registers) -> Result<(), Exception> {
+            Ok(())
+        }
+    }
+
+    impl Device for DataStore<Model, Model> {
+        type InputRegisters = Model;
+        type HoldingRegisters = Model;
+
+        fn service_call(&mut self, req: Request) -> future::Ready<Result<Response, Exception>> {
+            DataStore::service_call(self, &mut Model, req)
+        }
+
+        fn update_state(&mut self, _elapsed: Duration) {}
+    }
+
+    fn call(data_store: &mut DataStore<Model, Model>, req: Request) -> Result<Response, Exception> {
+        DataStore::service_call(data_store, &mut Model, req).into_inner()
+    }
+
+    #[test]
+    fn test_coils() {
+        let mut data_store = DataStore::<Model, Model>::default();
+        data_store.coils.insert(0, vec![true, false, true]);
+        data_store
+            .discrete_inputs
+            .insert(Address::MAX, vec![true, true]);
+
+        assert_eq!(
+            call(&mut data_store, Request::ReadCoils(0, 3)),
+            Ok(Response::ReadCoils(vec![true, false, true]))
+        );
+        assert_eq!(
+            call(&mut data_store, Request::ReadCoils(2, 2)),
+            Err(Exception::IllegalDataAddress)
+        );
+        assert_eq!(
+            call(&mut data_store, Request::WriteSingleCoil(1, true)),
+            Ok(Response::WriteSingleCoil(1, true))
+        );
+        assert_eq!(
+            call(
+                &mut data_store,
+                Request::WriteMultipleCoils(0, Cow::Owned(vec![false, false]))
+            ),
+            Ok(Response::WriteMultipleCoils(0, 2))
+        );
+        // Nothing is written if any coil is missing
+        assert_eq!(
+            call(
+                &mut data_store,
+                Request::WriteMultipleCoils(2, Cow::Owned(vec![false, false]))
+            ),
+            Err(Exception::IllegalDataAddress)
+        );
+        assert_eq!(
+            call(&mut data_store, Request::ReadCoils(0, 3)),
+            Ok(Response::ReadCoils(vec![false, false, true]))
+        );
+
+        // Coils beyond the address space are dropped
+        assert_eq!(
+            call(
+                &mut data_store,
+                Request::ReadDiscreteInputs(Address::MAX, 1)
+            ),
+            Ok(Response::ReadDiscreteInputs(vec![true]))
+        );
+        assert_eq!(
+            call(
+                &mut data_store,
+                Request::ReadDiscreteInputs(Address::MAX, 2)
+            ),
+            Err(Exception::IllegalDataAddress)
+        );
+    }
+
+    #[test]
+    fn test_read_write_multiple_registers() {
+        let mut data_store = DataStore::<Model, Model>::default();
+        assert_eq!(
+            call(
+                &mut data_store,
+                Request::ReadWriteMultipleRegisters(1, 2, 0, Cow::Owned(vec![10]))
+            ),
+            Ok(Response::ReadWriteMultipleRegisters(vec![2, 3]))
+        );
+
+        // Nothing is written if the read range is invalid
+        assert_eq!(
+            call(
+                &mut data_store,
+                Request::ReadWriteMultipleRegisters(2, 2, 0, Cow::Owned(vec![20]))
+            ),
+            Err(Exception::IllegalDataAddress)
+        );
+        assert_eq!(data_store.holding_registers.read(0, 1), Ok(vec![10]));
+    }
+
+    #[derive(Debug, Default)]
+    /// Model rejecting values over 100.
+    struct Limited(u16);
+
+    impl HoldingRegisterModel for Limited {
+        fn new_registers(&self) -> Registers {
+            let mut registers = Registers::default();
+            registers.insert(0, vec![self.0]);
+            registers
+        }
+
+        fn update_registers(&self, registers: &mut Registers) -> Result<(), Exception> {
+            registers.write(0, &[self.0])
+        }
+
+        fn update_self(&mut self, registers: &Registers) -> Result<(), Exception> {
+            let value = registers.read(0, 1)?[0];
+            if value > 100 {
+                return Err(Exception::IllegalDataValue);
+            }
+            self.0 = value;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_rollback() {
+        let mut hr = Limited(1);
+        let mut data_store = DataStore::<(), Limited>::new(&(), &hr);
+        let mut call =
+            |hr: &mut Limited, request| data_store.service_call(hr, request).into_inner();
+
+        assert!(call(&mut hr, Request::WriteSingleRegister(0, 50)).is_ok());
+        assert_eq!(hr.0, 50);
+        assert_eq!(
+            call(&mut hr, Request::WriteSingleRegister(0, 200)),
+            Err(Exception::IllegalDataValue)
+        );
+        assert_eq!(hr.0, 50);
+        assert_eq!(
+            call(&mut hr, Request::ReadHoldingRegisters(0, 1)),
+            Ok(Response::ReadHoldingRegisters(vec![50]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_multi_simulator() {
+        let device = |value| {
+            let mut data_store = DataStore::<Model, Model>::default();
+            data_store.holding_registers.write(0, &[value]).unwrap();
+            Simulator::new(data_store)
+        };
+        let multi = MultiSimulator::new()
+            .with_slave(Slave(1), device(1))
+            .with_slave(Slave(2), device(2));
+        let call = |multi: &MultiSimulator, slave, request| {
+            Simulation::call(multi, SlaveRequest { slave, request })
+        };
+        let read = Request::ReadHoldingRegisters(0, 1);
+
+        assert_eq!(
+            call(&multi, 1, read.clone()).await,
+            Reply::Response(Ok(Response::ReadHoldingRegisters(vec![1])))
+        );
+        assert_eq!(
+            call(&multi, 2, read.clone()).await,
+            Reply::Response(Ok(Response::ReadHoldingRegisters(vec![2])))
+        );
+        assert_eq!(
+            call(&multi, 3, read.clone()).await,
+            Reply::Response(Err(Exception::GatewayTargetDevice))
+        );
+
+        // Broadcast is served by all slaves without a reply
+        assert_eq!(
+            call(&multi, 0, Request::WriteSingleRegister(0, 7)).await,
+            Reply::NoResponse
+        );
+        for slave in [1, 2] {
+            assert_eq!(
+                call(&multi, slave, read.clone()).await,
+                Reply::Response(Ok(Response::ReadHoldingRegisters(vec![7])))
+            );
+        }
+
+        let multi = multi.without_unknown_slave_response();
+        assert_eq!(call(&multi, 3, read).await, Reply::NoResponse);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_state_updates() {
+        let simulator = TcpSimulator::new(
+            "127.0.0.1:0".parse().unwrap(),
+            Simulator::new(DataStore::<Model, Model>::default()),
+        );
+        let zero_period = simulator.clone().with_state_update_period(Duration::ZERO);
+        let err = zero_period.clone().spawn().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = zero_period.run_until(future::pending()).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        for time_scale in [-1.0, f64::NAN, f64::INFINITY] {
+            let simulator = simulator.clone().with_time_scale(time_scale);
+            let err = simulator.spawn().await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn test_server_id() {
+        let server_id = ServerId {
+            id: 0x42,
+            run_indicator_status: true,
+            additional_data: b"sim".to_vec(),
+        };
+        assert_eq!(
+            server_id.encode().unwrap(),
+            vec![5, 0x42, 0xFF, b's', b'i', b'm']
+        );
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut registers = Registers::default();
+        registers.insert(10, vec![1, 2]);
+        registers.insert(20, vec![5]);
+        registers.insert(0, vec![0; 3]);
+        // Overlapping and adjacent segments are merged
+        registers.insert(11, vec![3, 4]);
+        registers.insert(13, vec![0; 7]);
+        assert_eq!(registers.0 .0.len(), 2);
+        assert_eq!(registers.read(10, 4), Ok(vec![1, 3, 4, 0]));
+        assert_eq!(registers.read(19, 2), Ok(vec![0, 5]));
+        assert_eq!(registers.read(2, 2), Err(Exception::IllegalDataAddress));
+        assert_eq!(
+            registers.write(20, &[6, 7]),
+            Err(Exception::IllegalDataAddress)
+        );
+        assert_eq!(registers.read(20, 1), Ok(vec![5]));
+
+        registers.set(10, -2.5f32, WordOrder::LittleEndian).unwrap();
+        assert_eq!(registers.read(10, 2), Ok(vec![0x0000, 0xC020]));
+        assert_eq!(registers.get::<f32>(10, WordOrder::LittleEndian), Ok(-2.5));
+        assert_eq!(
+            registers.get::<u32>(10, WordOrder::BigEndian),
+            Ok(0x0000_C020)
+        );
+
+        registers.insert(Address::MAX, vec![1, 2]);
+        assert_eq!(registers.read(Address::MAX, 1), Ok(vec![1]));
+    }
+
+    #[derive(Debug, Clone, Default)]
+    /// Simulation accumulating the elapsed time of the state updates.
+    struct Clock(Arc<std::sync::Mutex<Duration>>);
+
+    impl Simulation for Clock {
+        fn call(&self, _req: SlaveRequest<'static>) -> BoxFuture<'static, Reply> {
+            future::ready(Reply::NoResponse).boxed()
+        }
+
+        fn update_state(&self, elapsed: Duration) -> BoxFuture<'static, ()> {
+            *self.0.lock().unwrap() += elapsed;
+            future::ready(()).boxed()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_state_update_clock() {
+        let clock = Clock::default();
+        let shutdown = tokio::time::sleep(Duration::from_millis(10_500));
+        run_until(
+            future::pending(),
+            clock.clone(),
+            Duration::from_secs(1),
+            2.0,
+            shutdown,
+        )
+        .await
+        .unwrap();
+        // Updates at 0, 1, ..., 10 seconds of virtual time scaled by 2
+        assert_eq!(*clock.0.lock().unwrap(), Duration::from_secs(20));
+    }
 }
@@ -0,0 +1,116 @@
+use std::{fmt::Debug, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use futures::future::{BoxFuture, FutureExt};
+use tokio::sync::Mutex;
+use tokio_modbus::{prelude::SlaveRequest, Exception, Request, Response};
+
+use super::{Device, Reply, Simulation};
+
+#[async_trait]
+/// Asynchronous Modbus device simulator trait, e.g. to call other simulated devices
+/// (by [`super::LocalClient`]) or a physics model running in another task.
+///
+/// Every [Device] is an [AsyncDevice] too, so the sync devices can be run by [AsyncSimulator] as well.
+pub trait AsyncDevice: Send + 'static {
+    async fn service_call(&mut self, req: Request<'static>) -> Result<Response, Exception>;
+
+    /// Update the device state by `elapsed` time since the previous update.
+    async fn update_state(&mut self, elapsed: Duration);
+}
+
+#[async_trait]
+impl<D: Device + Send + 'static> AsyncDevice for D {
+    async fn service_call(&mut self, req: Request<'static>) -> Result<Response, Exception> {
+        Device::service_call(self, req).await
+    }
+
+    async fn update_state(&mut self, elapsed: Duration) {
+        Device::update_state(self, elapsed)
+    }
+}
+
+/// Simulation of [AsyncDevice] guarded by asynchronous mutex,
+/// so the device can be awaited without blocking the runtime.
+pub struct AsyncSimulator<D>(pub Arc<Mutex<D>>);
+
+impl<D: AsyncDevice> AsyncSimulator<D> {
+    pub fn new(device: D) -> Self {
+        Self(Arc::new(Mutex::new(device)))
+    }
+}
+
+impl<D> Clone for AsyncSimulator<D> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<D> Debug for AsyncSimulator<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AsyncSimulator").finish_non_exhaustive()
+    }
+}
+
+impl<D: AsyncDevice> Simulation for AsyncSimulator<D> {
+    fn call(&self, req: SlaveRequest<'static>) -> BoxFuture<'static, Reply> {
+        let device = self.0.clone();
+        async move { device.lock().await.service_call(req.request).await.into() }.boxed()
+    }
+
+    fn update_state(&self, elapsed: Duration) -> BoxFuture<'static, ()> {
+        let device = self.0.clone();
+        async move { device.lock().await.update_state(elapsed).await }.boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::{testing::replay_device, LocalClient, ReplayDevice};
+    use tokio_modbus::{client::Client, slave::Slave};
+
+    /// Device forwarding the requests to another simulated device.
+    struct Gateway {
+        client: LocalClient<AsyncSimulator<ReplayDevice>>,
+        updates: usize,
+    }
+
+    #[async_trait]
+    impl AsyncDevice for Gateway {
+        async fn service_call(&mut self, req: Request<'static>) -> Result<Response, Exception> {
+            self.client
+                .call(req)
+                .await
+                .unwrap_or(Err(Exception::GatewayTargetDevice))
+        }
+
+        async fn update_state(&mut self, _elapsed: Duration) {
+            self.updates += 1;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_simulator() {
+        let device = replay_device([(
+            1,
+            Request::ReadHoldingRegisters(0, 1),
+            Response::ReadHoldingRegisters(vec![42]),
+        )]);
+        let gateway = AsyncSimulator::new(Gateway {
+            client: LocalClient::new(AsyncSimulator::new(device), Slave(1)),
+            updates: 0,
+        });
+
+        let req = SlaveRequest {
+            slave: 1,
+            request: Request::ReadHoldingRegisters(0, 1),
+        };
+        assert_eq!(
+            gateway.call(req).await,
+            Reply::Response(Ok(Response::ReadHoldingRegisters(vec![42])))
+        );
+        gateway.update_state(Duration::from_secs(1)).await;
+        assert_eq!(gateway.0.lock().await.updates, 1);
+    }
+}
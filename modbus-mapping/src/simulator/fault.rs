@@ -0,0 +1,224 @@
+use std::{
+    ops::RangeInclusive,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures::future::{BoxFuture, FutureExt};
+use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+use rand_distr::{Bernoulli, Distribution, Uniform};
+use tokio_modbus::{prelude::SlaveRequest, Address, Exception, Request};
+
+use super::{Reply, Simulation};
+
+#[derive(Debug, Clone)]
+/// Fault injection layer around [Simulation] (e.g. [`super::Simulator`]) to test client retry and timeout handling.
+///
+/// The faults are applied in this order:
+/// 1. delay of every response by latency,
+/// 2. exceptions for configured register addresses (of any register type),
+/// 3. unanswered requests with the given rate,
+/// 4. exceptions with the given rate,
+/// 5. corrupted responses with the given rate.
+///
+/// The random faults are reproducible for the given seed.
+pub struct FaultySimulation<S> {
+    simulation: S,
+    latency: Duration,
+    latency_jitter: Duration,
+    address_exceptions: Vec<(RangeInclusive<Address>, Exception)>,
+    no_response: Bernoulli,
+    exception: (Bernoulli, Exception),
+    corrupted_response: Bernoulli,
+    rng: Arc<Mutex<ChaCha8Rng>>,
+}
+
+impl<S: Simulation> FaultySimulation<S> {
+    /// Wrap `simulation` with no faults enabled.
+    pub fn new(simulation: S) -> Self {
+        let never = Bernoulli::new(0.0).unwrap();
+        Self {
+            simulation,
+            latency: Duration::ZERO,
+            latency_jitter: Duration::ZERO,
+            address_exceptions: vec![],
+            no_response: never,
+            exception: (never, Exception::ServerDeviceBusy),
+            corrupted_response: never,
+            rng: Arc::new(Mutex::new(ChaCha8Rng::seed_from_u64(0))),
+        }
+    }
+
+    /// Seed the random number generator of the random faults.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Arc::new(Mutex::new(ChaCha8Rng::seed_from_u64(seed)));
+        self
+    }
+
+    /// Delay each response by `latency` plus uniformly distributed random delay up to `jitter`.
+    pub fn with_latency(mut self, latency: Duration, jitter: Duration) -> Self {
+        self.latency = latency;
+        self.latency_jitter = jitter;
+        self
+    }
+
+    /// Return `exception` for each request touching `addrs` registers.
+    pub fn with_address_exception(
+        mut self,
+        addrs: RangeInclusive<Address>,
+        exception: Exception,
+    ) -> Self {
+        self.address_exceptions.push((addrs, exception));
+        self
+    }
+
+    /// Leave requests unanswered with probability `rate`.
+    pub fn with_no_response_rate(mut self, rate: f64) -> Self {
+        self.no_response = bernoulli(rate);
+        self
+    }
+
+    /// Return `exception` (e.g. [Exception::ServerDeviceBusy]) with probability `rate`.
+    pub fn with_exception_rate(mut self, rate: f64, exception: Exception) -> Self {
+        self.exception = (bernoulli(rate), exception);
+        self
+    }
+
+    /// Corrupt responses with probability `rate`.
+    pub fn with_corrupted_response_rate(mut self, rate: f64) -> Self {
+        self.corrupted_response = bernoulli(rate);
+        self
+    }
+
+    /// Wrapped simulation.
+    pub fn simulation(&self) -> &S {
+        &self.simulation
+    }
+}
+
+fn bernoulli(rate: f64) -> Bernoulli {
+    Bernoulli::new(rate).unwrap_or_else(|_| panic!("Fault rate {rate} is not in [0, 1] range."))
+}
+
+/// Register address ranges touched by the request.
+fn request_addrs(req: &Request) -> Vec<RangeInclusive<Address>> {
+    let range = |addr: Address, cnt: usize| addr..=addr.saturating_add(cnt.max(1) as u16 - 1);
+    match req {
+        Request::ReadCoils(addr, cnt)
+        | Request::ReadDiscreteInputs(addr, cnt)
+        | Request::ReadInputRegisters(addr, cnt)
+        | Request::ReadHoldingRegisters(addr, cnt) => vec![range(*addr, *cnt as usize)],
+        Request::WriteSingleCoil(addr, _)
+        | Request::WriteSingleRegister(addr, _)
+        | Request::MaskWriteRegister(addr, _, _) => vec![range(*addr, 1)],
+        Request::WriteMultipleCoils(addr, coils) => vec![range(*addr, coils.len())],
+        Request::WriteMultipleRegisters(addr, words) => vec![range(*addr, words.len())],
+        Request::ReadWriteMultipleRegisters(read_addr, cnt, write_addr, words) => vec![
+            range(*read_addr, *cnt as usize),
+            range(*write_addr, words.len()),
+        ],
+        Request::Custom(..) | Request::Disconnect => vec![],
+    }
+}
+
+fn overlaps(a: &RangeInclusive<Address>, b: &RangeInclusive<Address>) -> bool {
+    a.start() <= b.end() && b.start() <= a.end()
+}
+
+impl<S: Simulation> Simulation for FaultySimulation<S> {
+    fn call(&self, req: SlaveRequest<'static>) -> BoxFuture<'static, Reply> {
+        let (delay, no_response, exception, corrupted) = {
+            let mut rng = self.rng.lock().unwrap();
+            let jitter = Uniform::new_inclusive(Duration::ZERO, self.latency_jitter);
+            (
+                self.latency + jitter.sample(&mut *rng),
+                self.no_response.sample(&mut *rng),
+                self.exception.0.sample(&mut *rng),
+                self.corrupted_response.sample(&mut *rng),
+            )
+        };
+
+        let addrs = request_addrs(&req.request);
+        let address_exception = self
+            .address_exceptions
+            .iter()
+            .find(|(range, _)| addrs.iter().any(|addr| overlaps(range, addr)))
+            .map(|(_, exception)| *exception);
+
+        let simulation = self.simulation.clone();
+        let exception = exception.then_some(self.exception.1);
+        async move {
+            tokio::time::sleep(delay).await;
+            if let Some(exception) = address_exception {
+                Reply::Response(Err(exception))
+            } else if no_response {
+                Reply::NoResponse
+            } else if let Some(exception) = exception {
+                Reply::Response(Err(exception))
+            } else {
+                match simulation.call(req).await {
+                    Reply::Response(result) if corrupted => Reply::CorruptedResponse(result),
+                    reply => reply,
+                }
+            }
+        }
+        .boxed()
+    }
+
+    fn update_state(&self, elapsed: Duration) -> BoxFuture<'static, ()> {
+        self.simulation.update_state(elapsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use tokio_modbus::Response;
+
+    #[derive(Debug, Clone)]
+    struct OkSimulation;
+
+    impl Simulation for OkSimulation {
+        fn call(&self, _req: SlaveRequest<'static>) -> BoxFuture<'static, Reply> {
+            future::ready(Reply::Response(Ok(Response::WriteSingleRegister(0, 0)))).boxed()
+        }
+
+        fn update_state(&self, _elapsed: Duration) -> BoxFuture<'static, ()> {
+            future::ready(()).boxed()
+        }
+    }
+
+    fn read(addr: Address, cnt: u16) -> SlaveRequest<'static> {
+        SlaveRequest {
+            slave: 1,
+            request: Request::ReadInputRegisters(addr, cnt),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_faulty_simulation() {
+        let simulation = FaultySimulation::new(OkSimulation)
+            .with_address_exception(10..=19, Exception::IllegalDataAddress)
+            .with_corrupted_response_rate(1.0);
+        assert_eq!(
+            simulation.call(read(5, 5)).await,
+            Reply::CorruptedResponse(Ok(Response::WriteSingleRegister(0, 0)))
+        );
+        assert_eq!(
+            simulation.call(read(5, 6)).await,
+            Reply::Response(Err(Exception::IllegalDataAddress))
+        );
+
+        let simulation = FaultySimulation::new(OkSimulation)
+            .with_seed(42)
+            .with_no_response_rate(0.5);
+        let mut no_responses = 0;
+        for _ in 0..1000 {
+            if simulation.call(read(0, 1)).await == Reply::NoResponse {
+                no_responses += 1;
+            }
+        }
+        assert!((400..600).contains(&no_responses));
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    fmt::Debug,
+    io,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use tokio_modbus::{
+    client::{Client, Context},
+    prelude::SlaveRequest,
+    slave::{Slave, SlaveContext},
+    Request, Response,
+};
+
+use super::{Reply, Simulation};
+
+#[derive(Clone)]
+/// In-process client calling the [Simulation] directly without any transport.
+///
+/// Convert it into [Context] to use it with [`crate::core::InputRegisterMap`] and [`crate::core::HoldingRegisterMap`] in tests.
+/// All issued requests are recorded for later assertions, the clones share the record.
+/// Unanswered and corrupted [Reply] is returned as timed out and invalid data IO error respectively.
+pub struct LocalClient<S> {
+    simulation: S,
+    slave: Slave,
+    requests: Arc<Mutex<Vec<SlaveRequest<'static>>>>,
+}
+
+impl<S: Simulation> LocalClient<S> {
+    /// Create client issuing requests to `slave` of `simulation`.
+    pub fn new(simulation: S, slave: Slave) -> Self {
+        Self {
+            simulation,
+            slave,
+            requests: Arc::default(),
+        }
+    }
+
+    /// Simulation the client is connected to.
+    pub fn simulation(&self) -> &S {
+        &self.simulation
+    }
+
+    /// Requests issued so far.
+    pub fn requests(&self) -> Vec<SlaveRequest<'static>> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Take the requests issued so far and clear the record.
+    pub fn take_requests(&self) -> Vec<SlaveRequest<'static>> {
+        std::mem::take(&mut self.requests.lock().unwrap())
+    }
+}
+
+impl<S> Debug for LocalClient<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocalClient")
+            .field("slave", &self.slave)
+            .field("requests", &self.requests)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> SlaveContext for LocalClient<S> {
+    fn set_slave(&mut self, slave: Slave) {
+        self.slave = slave;
+    }
+}
+
+#[async_trait]
+impl<S: Simulation> Client for LocalClient<S> {
+    async fn call(&mut self, request: Request<'_>) -> tokio_modbus::Result<Response> {
+        if let Request::Disconnect = request {
+            // There is no connection to close
+            return Err(io::Error::from(io::ErrorKind::NotConnected).into());
+        }
+        let req = SlaveRequest {
+            slave: self.slave.into(),
+            request: request.into_owned(),
+        };
+        self.requests.lock().unwrap().push(req.clone());
+
+        match self.simulation.call(req).await {
+            Reply::Response(result) => Ok(result),
+            Reply::NoResponse => Err(io::Error::new(io::ErrorKind::TimedOut, "No response").into()),
+            Reply::CorruptedResponse(_) => {
+                Err(io::Error::new(io::ErrorKind::InvalidData, "Corrupted response").into())
+            }
+        }
+    }
+}
+
+impl<S: Simulation> From<LocalClient<S>> for Context {
+    fn from(client: LocalClient<S>) -> Self {
+        Context::from(Box::new(client) as Box<dyn Client>)
+    }
+}
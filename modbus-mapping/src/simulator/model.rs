@@ -0,0 +1,72 @@
+use std::{fmt::Debug, time::Duration};
+
+use futures::future;
+use tokio_modbus::{Exception, Request, Response};
+
+use super::{DataStore, Device, HoldingRegisterModel, InputRegisterModel};
+
+#[derive(Clone)]
+/// Generic [Device] wiring input and holding register models to [DataStore].
+///
+/// The `update` closure gets the models and the elapsed time on each state update,
+/// then both models are synced with the data store.
+/// Holding register writes of the clients update `hr` by [HoldingRegisterModel::update_self].
+pub struct ModelDevice<I, H, F> {
+    pub ir: I,
+    pub hr: H,
+    pub data_store: DataStore<I, H>,
+    update: F,
+}
+
+impl<I, H, F> ModelDevice<I, H, F>
+where
+    I: Default + InputRegisterModel,
+    H: Default + HoldingRegisterModel,
+    F: FnMut(&mut I, &mut H, Duration),
+{
+    /// Create device from the initial register values and the `update` closure.
+    pub fn new(ir: I, hr: H, update: F) -> Self {
+        let data_store = DataStore::new(&ir, &hr);
+        Self {
+            ir,
+            hr,
+            data_store,
+            update,
+        }
+    }
+}
+
+impl<I: Debug, H: Debug, F> Debug for ModelDevice<I, H, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ModelDevice")
+            .field("ir", &self.ir)
+            .field("hr", &self.hr)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<I, H, F> Device for ModelDevice<I, H, F>
+where
+    I: Default + InputRegisterModel,
+    H: Default + HoldingRegisterModel,
+    F: FnMut(&mut I, &mut H, Duration),
+{
+    type InputRegisters = I;
+    type HoldingRegisters = H;
+
+    fn service_call(&mut self, req: Request) -> future::Ready<Result<Response, Exception>> {
+        self.data_store.service_call(&mut self.hr, req)
+    }
+
+    fn update_state(&mut self, elapsed: Duration) {
+        (self.update)(&mut self.ir, &mut self.hr, elapsed);
+
+        // Sync with data_store
+        let _ = self
+            .ir
+            .update_registers(&mut self.data_store.input_registers);
+        let _ = self
+            .hr
+            .update_registers(&mut self.data_store.holding_registers);
+    }
+}
@@ -0,0 +1,188 @@
+use std::{fs::File, io, io::BufReader, path::Path, time::Duration};
+
+use futures::future;
+use tokio_modbus::{slave::SlaveId, Exception, Request, Response};
+
+use super::{DataStore, Device, ServerId, REPORT_SERVER_ID};
+use crate::record::{read_records, Record};
+
+#[derive(Debug, Clone)]
+/// Device serving register values captured by [`crate::record::Recorder`] over time.
+///
+/// The values of successful reads and writes are applied to the data store
+/// once the simulation time, accumulated by the state updates, reaches their recording time.
+/// Before that, each register holds the first value it was recorded with,
+/// so the whole recorded register map is readable from the start.
+/// Recorded exceptions are not replayed.
+pub struct ReplayDevice {
+    pub data_store: DataStore<(), ()>,
+    records: Vec<Record>,
+    next: usize,
+    time: Duration,
+    looping: bool,
+}
+
+impl ReplayDevice {
+    /// Create device replaying `records` ordered by their time.
+    pub fn new(records: Vec<Record>) -> Self {
+        let mut data_store = DataStore::default();
+        for record in records.iter().rev() {
+            apply(&mut data_store, record);
+        }
+
+        Self {
+            data_store,
+            records,
+            next: 0,
+            time: Duration::ZERO,
+            looping: false,
+        }
+    }
+
+    /// Create device replaying records of `slave` from recording file at `path`.
+    pub fn open(path: impl AsRef<Path>, slave: SlaveId) -> io::Result<Self> {
+        let records = read_records(BufReader::new(File::open(path)?))?
+            .into_iter()
+            .filter(|record| record.slave == slave)
+            .collect();
+
+        Ok(Self::new(records))
+    }
+
+    /// Restart the replay from the beginning after the last record.
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// Whether all records were replayed.
+    pub fn is_finished(&self) -> bool {
+        self.next == self.records.len()
+    }
+}
+
+/// Apply values of the successful `record` exchange to `data_store`.
+fn apply(data_store: &mut DataStore<(), ()>, record: &Record) {
+    let Ok(response) = &record.response else {
+        return;
+    };
+    match (&record.request, response) {
+        (Request::ReadCoils(addr, _), Response::ReadCoils(bits)) => {
+            data_store.coils.insert(*addr, bits.clone())
+        }
+        (Request::ReadDiscreteInputs(addr, _), Response::ReadDiscreteInputs(bits)) => {
+            data_store.discrete_inputs.insert(*addr, bits.clone())
+        }
+        (Request::WriteSingleCoil(addr, bit), _) => data_store.coils.insert(*addr, vec![*bit]),
+        (Request::WriteMultipleCoils(addr, bits), _) => {
+            data_store.coils.insert(*addr, bits.to_vec())
+        }
+        (Request::ReadInputRegisters(addr, _), Response::ReadInputRegisters(words)) => {
+            data_store.input_registers.insert(*addr, words.clone())
+        }
+        (Request::ReadHoldingRegisters(addr, _), Response::ReadHoldingRegisters(words)) => {
+            data_store.holding_registers.insert(*addr, words.clone())
+        }
+        (Request::WriteSingleRegister(addr, word), _) => {
+            data_store.holding_registers.insert(*addr, vec![*word])
+        }
+        (Request::WriteMultipleRegisters(addr, words), _) => {
+            data_store.holding_registers.insert(*addr, words.to_vec())
+        }
+        (
+            Request::ReadWriteMultipleRegisters(read_addr, _, write_addr, write_words),
+            Response::ReadWriteMultipleRegisters(words),
+        ) => {
+            data_store
+                .holding_registers
+                .insert(*write_addr, write_words.to_vec());
+            data_store
+                .holding_registers
+                .insert(*read_addr, words.clone());
+        }
+        (Request::Custom(REPORT_SERVER_ID, _), Response::Custom(REPORT_SERVER_ID, data)) => {
+            if let Some(server_id) = ServerId::decode(data) {
+                data_store.server_id = server_id;
+            }
+        }
+        _ => {}
+    }
+}
+
+impl Device for ReplayDevice {
+    type InputRegisters = ();
+    type HoldingRegisters = ();
+
+    fn service_call(&mut self, req: Request) -> future::Ready<Result<Response, Exception>> {
+        self.data_store.service_call(&mut (), req)
+    }
+
+    fn update_state(&mut self, elapsed: Duration) {
+        self.time += elapsed;
+        while let Some(record) = self.records.get(self.next) {
+            if self.time < record.elapsed {
+                break;
+            }
+            apply(&mut self.data_store, record);
+            self.next += 1;
+
+            if self.looping && self.next == self.records.len() && !record.elapsed.is_zero() {
+                self.time -= record.elapsed;
+                self.next = 0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(secs: u64, addr: u16, words: Vec<u16>) -> Record {
+        Record {
+            elapsed: Duration::from_secs(secs),
+            slave: 1,
+            request: Request::ReadInputRegisters(addr, words.len() as u16),
+            response: Ok(Response::ReadInputRegisters(words)),
+        }
+    }
+
+    #[test]
+    fn test_replay_device() {
+        let mut device = ReplayDevice::new(vec![
+            read(0, 0, vec![1, 2]),
+            read(0, 2, vec![3]),
+            read(3600, 0, vec![10, 20, 30]),
+        ]);
+        let read_all = |device: &mut ReplayDevice| {
+            device
+                .service_call(Request::ReadInputRegisters(0, 3))
+                .into_inner()
+        };
+        assert_eq!(
+            read_all(&mut device),
+            Ok(Response::ReadInputRegisters(vec![1, 2, 3]))
+        );
+
+        // The last record is far in the future
+        device.update_state(Duration::from_secs(60));
+        assert!(!device.is_finished());
+        assert_eq!(
+            read_all(&mut device),
+            Ok(Response::ReadInputRegisters(vec![1, 2, 3]))
+        );
+        assert_eq!(
+            device
+                .service_call(Request::ReadInputRegisters(3, 1))
+                .into_inner(),
+            Err(Exception::IllegalDataAddress)
+        );
+
+        device.update_state(Duration::from_secs(3540));
+        assert!(device.is_finished());
+        assert_eq!(
+            read_all(&mut device),
+            Ok(Response::ReadInputRegisters(vec![10, 20, 30]))
+        );
+    }
+}
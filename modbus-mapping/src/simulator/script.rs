@@ -0,0 +1,411 @@
+use std::{
+    f64::consts::TAU,
+    fs,
+    io::{self, BufRead, BufReader},
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use futures::future;
+use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+use rand_distr::{Distribution, StandardNormal, Uniform};
+use tokio_modbus::{Exception, Request, Response};
+
+use super::{DataStore, Device, HoldingRegisterModel, InputRegisterModel};
+
+#[derive(Debug, Clone, PartialEq)]
+/// Generator of the actual field value over the simulation time.
+pub enum Generator {
+    /// Constant value.
+    Constant(f64),
+    /// Linear ramp from `start` changing by `slope` per second.
+    Ramp { start: f64, slope: f64 },
+    /// Sine wave `offset + amplitude * sin(2π t / period)`.
+    Sine {
+        offset: f64,
+        amplitude: f64,
+        period: Duration,
+    },
+    /// Random walk from `start` by uniformly distributed steps up to `step` per state update, clamped to `min..=max`.
+    RandomWalk {
+        start: f64,
+        step: f64,
+        min: f64,
+        max: f64,
+    },
+    /// Normally distributed noise.
+    Noise { mean: f64, std_dev: f64 },
+    /// Playback of `(time, value)` samples holding the last value.
+    Samples(Vec<(Duration, f64)>),
+}
+
+impl Generator {
+    /// Load [Generator::Samples] from CSV file with time (in seconds) in the first column
+    /// and value in the `column` column. Lines without numbers (e.g. header) are skipped.
+    pub fn from_csv(path: impl AsRef<Path>, column: usize) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        let mut samples = vec![];
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let cells = line.split(',').map(str::trim).collect::<Vec<_>>();
+            let time = cells.first().and_then(|cell| cell.parse::<f64>().ok());
+            let value = cells.get(column).and_then(|cell| cell.parse::<f64>().ok());
+            if let (Some(time), Some(value)) = (time, value) {
+                let time = Duration::try_from_secs_f64(time)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                samples.push((time, value));
+            }
+        }
+        samples.sort_by_key(|(time, _)| *time);
+
+        Ok(Self::Samples(samples))
+    }
+
+    /// Check the generator arguments sampled without panics or NaN values.
+    fn validate(&self) -> Result<(), String> {
+        let finite = |args: &[f64]| args.iter().all(|arg| arg.is_finite());
+        match self {
+            Self::Constant(value) if !value.is_finite() => Err("Value must be finite".into()),
+            Self::Ramp { start, slope } if !finite(&[*start, *slope]) => {
+                Err("Ramp start and slope must be finite".into())
+            }
+            Self::Sine {
+                offset,
+                amplitude,
+                period,
+            } if !finite(&[*offset, *amplitude]) || period.is_zero() => {
+                Err("Sine offset and amplitude must be finite and period non-zero".into())
+            }
+            Self::RandomWalk {
+                start,
+                step,
+                min,
+                max,
+            } if !finite(&[*start, *step, *min, *max]) || min > max => {
+                Err("Random walk arguments must be finite with min not greater than max".into())
+            }
+            Self::Noise { mean, std_dev } if !finite(&[*mean, *std_dev]) || *std_dev < 0.0 => {
+                Err("Noise mean must be finite and standard deviation non-negative".into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Initial value of stateful generators.
+    fn initial(&self) -> f64 {
+        match self {
+            Self::RandomWalk { start, .. } => *start,
+            _ => f64::NAN,
+        }
+    }
+
+    /// Value at `elapsed` time given the `previous` value.
+    fn sample(&self, elapsed: Duration, previous: f64, rng: &mut ChaCha8Rng) -> f64 {
+        let t = elapsed.as_secs_f64();
+        match self {
+            Self::Constant(value) => *value,
+            Self::Ramp { start, slope } => start + slope * t,
+            Self::Sine {
+                offset,
+                amplitude,
+                period,
+            } => offset + amplitude * (TAU * t / period.as_secs_f64()).sin(),
+            Self::RandomWalk { step, min, max, .. } => {
+                let step = Uniform::new_inclusive(-step.abs(), step.abs()).sample(rng);
+                (previous + step).clamp(*min, *max)
+            }
+            Self::Noise { mean, std_dev } => {
+                let z: f64 = StandardNormal.sample(rng);
+                mean + std_dev * z
+            }
+            Self::Samples(samples) => {
+                let i = samples.partition_point(|(time, _)| *time <= elapsed);
+                samples
+                    .get(i.saturating_sub(1))
+                    .map_or(f64::NAN, |(_, value)| *value)
+            }
+        }
+    }
+
+    /// Parse generator from `<kind> <args>...` words, e.g. `sine 230 10 60`.
+    /// The `csv` file path is relative to `base` directory.
+    fn parse(words: &[&str], base: &Path) -> Result<Self, String> {
+        let (kind, args) = words.split_first().ok_or("Missing generator")?;
+        if *kind == "csv" {
+            let (path, column) = match args {
+                [path] => (path, 1),
+                [path, column] => (path, column.parse().map_err(|_| "Invalid CSV column")?),
+                _ => return Err("Expected `csv <path> [column]`".into()),
+            };
+            return Self::from_csv(base.join(path), column).map_err(|err| err.to_string());
+        }
+
+        let args = args
+            .iter()
+            .map(|arg| arg.parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("Invalid generator argument: {err}"))?;
+        let generator = match (*kind, args.as_slice()) {
+            ("constant", [value]) => Self::Constant(*value),
+            ("ramp", [start, slope]) => Self::Ramp {
+                start: *start,
+                slope: *slope,
+            },
+            ("sine", [offset, amplitude, period]) => Self::Sine {
+                offset: *offset,
+                amplitude: *amplitude,
+                period: Duration::try_from_secs_f64(*period).map_err(|err| err.to_string())?,
+            },
+            ("random_walk", [start, step, min, max]) => Self::RandomWalk {
+                start: *start,
+                step: *step,
+                min: *min,
+                max: *max,
+            },
+            ("noise", [mean, std_dev]) => Self::Noise {
+                mean: *mean,
+                std_dev: *std_dev,
+            },
+            _ => return Err(format!("Invalid generator `{}`", words.join(" "))),
+        };
+        generator.validate()?;
+
+        Ok(generator)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// Input register field generators driving [ScriptedDevice].
+///
+/// The script file contains `<field> = <generator> <args>...` lines and optional `seed = <u64>` line
+/// of the random generators. Empty lines and lines starting with `#` are ignored, e.g.
+///
+/// ```text
+/// seed = 42
+/// power = ramp 0 10            # start, slope per second
+/// voltage = sine 230 5 60      # offset, amplitude, period in seconds
+/// temperature = random_walk 25 0.1 20 30  # start, step, min, max
+/// grid_frequency = noise 50 0.1          # mean, standard deviation
+/// state_of_energy = csv soe.csv 1        # path relative to the script, value column
+/// setpoint = constant 0
+/// ```
+pub struct Script {
+    pub seed: u64,
+    pub fields: Vec<(String, Generator)>,
+}
+
+impl Script {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Seed the random number generator of the random generators.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Drive field `name` by `generator`.
+    pub fn with_field(mut self, name: &str, generator: Generator) -> Self {
+        self.fields.push((name.to_string(), generator));
+        self
+    }
+
+    /// Load script from file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text, path.parent().unwrap_or(Path::new("")))
+    }
+
+    fn parse(text: &str, base: &Path) -> io::Result<Self> {
+        let mut script = Self::new();
+        for (i, line) in text.lines().enumerate() {
+            let invalid = |msg: String| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {msg}", i + 1))
+            };
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (name, generator) = line
+                .split_once('=')
+                .ok_or_else(|| invalid("Expected `<field> = <generator>`".into()))?;
+            let (name, generator) = (name.trim(), generator.trim());
+            if name == "seed" {
+                script.seed = generator
+                    .parse()
+                    .map_err(|_| invalid("Invalid seed".into()))?;
+            } else {
+                let words = generator.split_whitespace().collect::<Vec<_>>();
+                let generator = Generator::parse(&words, base).map_err(invalid)?;
+                script.fields.push((name.to_string(), generator));
+            }
+        }
+
+        Ok(script)
+    }
+}
+
+impl FromStr for Script {
+    type Err = io::Error;
+
+    /// Parse script text; `csv` file paths are relative to the current directory.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::parse(text, Path::new(""))
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Device whose input register fields are driven by [Script] generators.
+///
+/// The generated values are set by [InputRegisterModel::set_field] with the simulation time accumulated by the state updates
+/// and synced to the data store by [InputRegisterModel::update_registers].
+/// Holding registers are served as usual.
+pub struct ScriptedDevice<I, H> {
+    pub ir: I,
+    pub hr: H,
+    pub data_store: DataStore<I, H>,
+    script: Script,
+    values: Vec<f64>,
+    rng: ChaCha8Rng,
+    time: Duration,
+    last_error: Option<Arc<io::Error>>,
+}
+
+impl<I, H> ScriptedDevice<I, H>
+where
+    I: Default + InputRegisterModel,
+    H: Default + HoldingRegisterModel,
+{
+    /// Create device from the initial register values and `script`.
+    ///
+    /// An error is returned if the script drives an unknown field of `ir` or a generator has invalid arguments.
+    pub fn new(ir: I, hr: H, script: Script) -> io::Result<Self> {
+        for (name, generator) in &script.fields {
+            generator.validate().map_err(|msg| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Field `{name}`: {msg}"),
+                )
+            })?;
+        }
+        let values = script.fields.iter().map(|(_, g)| g.initial()).collect();
+        let mut device = Self {
+            ir,
+            hr,
+            data_store: DataStore::default(),
+            rng: ChaCha8Rng::seed_from_u64(script.seed),
+            script,
+            values,
+            time: Duration::ZERO,
+            last_error: None,
+        };
+        device.apply(Duration::ZERO)?;
+        device
+            .hr
+            .update_registers(&mut device.data_store.holding_registers)
+            .map_err(|exc| io::Error::new(io::ErrorKind::InvalidData, exc))?;
+
+        Ok(device)
+    }
+
+    /// Error of the last state update, e.g. generated value out of the register range.
+    pub fn last_error(&self) -> Option<&io::Error> {
+        self.last_error.as_deref()
+    }
+
+    /// Set the generated values at `elapsed` time and sync them with the data store.
+    fn apply(&mut self, elapsed: Duration) -> io::Result<()> {
+        for ((name, generator), value) in self.script.fields.iter().zip(&mut self.values) {
+            *value = generator.sample(elapsed, *value, &mut self.rng);
+            if !self.ir.set_field(name, *value) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Unknown input register field `{name}`"),
+                ));
+            }
+        }
+        self.ir
+            .update_registers(&mut self.data_store.input_registers)
+            .map_err(|exc| io::Error::new(io::ErrorKind::InvalidData, exc))
+    }
+}
+
+impl<I, H> Device for ScriptedDevice<I, H>
+where
+    I: Default + InputRegisterModel,
+    H: Default + HoldingRegisterModel,
+{
+    type InputRegisters = I;
+    type HoldingRegisters = H;
+
+    fn service_call(&mut self, req: Request) -> future::Ready<Result<Response, Exception>> {
+        self.data_store.service_call(&mut self.hr, req)
+    }
+
+    fn update_state(&mut self, elapsed: Duration) {
+        self.time += elapsed;
+        self.last_error = self.apply(self.time).err().map(Arc::new);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script() {
+        let script: Script = "
+            # Battery scenario
+            seed = 7
+            power = ramp 100 -2.5
+            voltage = sine 230 10 60  # 1 minute period
+            temperature = random_walk 25 0.5 24.9 25.1
+            "
+        .parse()
+        .unwrap();
+        assert_eq!(script.seed, 7);
+        assert_eq!(
+            script.fields[0],
+            (
+                "power".to_string(),
+                Generator::Ramp {
+                    start: 100.0,
+                    slope: -2.5
+                }
+            )
+        );
+
+        let mut rng = ChaCha8Rng::seed_from_u64(script.seed);
+        let t = Duration::from_secs(15);
+        assert_eq!(script.fields[0].1.sample(t, f64::NAN, &mut rng), 62.5);
+        assert!((script.fields[1].1.sample(t, f64::NAN, &mut rng) - 240.0).abs() < 1e-9);
+        let temperature = script.fields[2].1.sample(t, 25.0, &mut rng);
+        assert!((24.9..=25.1).contains(&temperature));
+
+        let samples = Generator::Samples(vec![
+            (Duration::from_secs(1), 1.0),
+            (Duration::from_secs(3), 3.0),
+        ]);
+        assert_eq!(samples.sample(Duration::ZERO, f64::NAN, &mut rng), 1.0);
+        assert_eq!(
+            samples.sample(Duration::from_secs(2), f64::NAN, &mut rng),
+            1.0
+        );
+        assert_eq!(
+            samples.sample(Duration::from_secs(9), f64::NAN, &mut rng),
+            3.0
+        );
+
+        assert!("power = ramp 1".parse::<Script>().is_err());
+        assert!("power = sine 0 1 0".parse::<Script>().is_err());
+        assert!("power = random_walk 0 1 10 -10".parse::<Script>().is_err());
+        assert!("power = random_walk 0 NaN -10 10"
+            .parse::<Script>()
+            .is_err());
+        assert!("power = noise 0 -1".parse::<Script>().is_err());
+    }
+}
@@ -0,0 +1,39 @@
+//! Fixtures shared by the unit tests.
+use std::time::Duration;
+
+use tokio_modbus::{
+    client::Context,
+    slave::{Slave, SlaveId},
+    Request, Response,
+};
+
+use super::{LocalClient, ReplayDevice, Simulation, Simulator};
+use crate::record::Record;
+
+/// Device serving the `(slave, request, response)` exchanges recorded at the start.
+pub(crate) fn replay_device(
+    exchanges: impl IntoIterator<Item = (SlaveId, Request<'static>, Response)>,
+) -> ReplayDevice {
+    let records = exchanges
+        .into_iter()
+        .map(|(slave, request, response)| Record {
+            elapsed: Duration::ZERO,
+            slave,
+            request,
+            response: Ok(response),
+        })
+        .collect();
+    ReplayDevice::new(records)
+}
+
+/// Simulator of [replay_device].
+pub(crate) fn replay_simulator(
+    exchanges: impl IntoIterator<Item = (SlaveId, Request<'static>, Response)>,
+) -> Simulator<ReplayDevice> {
+    Simulator::new(replay_device(exchanges))
+}
+
+/// Context of [LocalClient] issuing requests to `slave` of `simulation`.
+pub(crate) fn local_context<S: Simulation>(simulation: S, slave: SlaveId) -> Context {
+    Context::from(LocalClient::new(simulation, Slave(slave)))
+}
@@ -0,0 +1,468 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Context, Poll},
+};
+
+use futures::future::{BoxFuture, FutureExt};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{TcpListener, TcpStream, UdpSocket},
+    sync::watch,
+    task::JoinSet,
+};
+use tokio_modbus::{prelude::SlaveRequest, server, Exception, Response};
+use tokio_serial::SerialStream;
+
+use super::{Reply, Simulation};
+use crate::frame::{self, MBAP_HEADER_LEN};
+
+/// Broadcast slave id of RTU requests which are never answered.
+const RTU_BROADCAST: u8 = 0;
+/// Maximum size of Modbus TCP ADU.
+const MAX_MBAP_FRAME_LEN: usize = 260;
+
+/// Timeout of the TLS handshake of an accepted connection, other connections are not accepted meanwhile.
+#[cfg(feature = "tls")]
+const TLS_HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// Framing of the requests and responses carried by TCP connections.
+pub(crate) enum Framing {
+    /// Modbus TCP (MBAP) frames.
+    #[default]
+    Mbap,
+    /// RTU frames (RTU over TCP), e.g. of a serial to TCP converter.
+    Rtu,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Fault of the next response frame written to the connection.
+enum FrameFault {
+    /// Discard the frame to leave the request unanswered.
+    Discard,
+    /// Corrupt the frame so that the client rejects it.
+    Corrupt,
+}
+
+type FaultSlot = Arc<Mutex<Option<FrameFault>>>;
+
+/// Byte stream of an accepted TCP connection, possibly secured by TLS.
+pub(crate) trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+#[derive(Debug, Clone, Default)]
+/// Options of the TCP connections.
+pub(crate) struct TcpOptions {
+    pub framing: Framing,
+    /// The connections are secured by TLS (Modbus/TCP Security).
+    #[cfg(feature = "tls")]
+    pub tls: Option<Arc<tokio_rustls::rustls::ServerConfig>>,
+}
+
+impl TcpOptions {
+    /// Stream of the accepted connection, `None` if it failed the TLS handshake.
+    async fn accept(&self, stream: TcpStream) -> Option<Box<dyn Stream>> {
+        #[cfg(feature = "tls")]
+        if let Some(config) = &self.tls {
+            let handshake = tokio_rustls::TlsAcceptor::from(config.clone()).accept(stream);
+            return match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, handshake).await {
+                Ok(Ok(stream)) => Some(Box::new(stream)),
+                Ok(Err(_)) | Err(_) => None,
+            };
+        }
+        Some(Box::new(stream))
+    }
+}
+
+/// Service serving [Simulation] to a single connection of tokio-modbus server.
+///
+/// The tokio-modbus servers answer every request, so the unanswered and corrupted [Reply]
+/// is handed over to the [FaultyStream] of the connection which discards or corrupts the response frame.
+/// Without the stream, such replies are answered with [Exception::GatewayTargetDevice].
+pub(crate) struct SimulationService<S> {
+    simulation: S,
+    fault: Option<FaultSlot>,
+    /// Discard the responses to RTU broadcast requests.
+    discard_broadcast: bool,
+}
+
+impl<S: Simulation> server::Service for SimulationService<S> {
+    type Request = SlaveRequest<'static>;
+    type Future = BoxFuture<'static, Result<Response, Exception>>;
+
+    fn call(&self, req: Self::Request) -> Self::Future {
+        let fault = self.fault.clone();
+        let broadcast = self.discard_broadcast && req.slave == RTU_BROADCAST;
+        self.simulation
+            .call(req)
+            .map(move |reply| {
+                let (result, frame_fault) = match reply {
+                    Reply::Response(result) | Reply::CorruptedResponse(result) if broadcast => {
+                        (result, FrameFault::Discard)
+                    }
+                    Reply::Response(result) => return result,
+                    // The response is discarded, so any will do
+                    Reply::NoResponse => (Err(Exception::GatewayTargetDevice), FrameFault::Discard),
+                    Reply::CorruptedResponse(result) => (result, FrameFault::Corrupt),
+                };
+                match fault {
+                    Some(fault) => {
+                        *fault.lock().unwrap() = Some(frame_fault);
+                        result
+                    }
+                    None => Err(Exception::GatewayTargetDevice),
+                }
+            })
+            .boxed()
+    }
+}
+
+/// Connection stream discarding or corrupting the response frames as requested by [SimulationService]
+/// and ending the connection once the server stops.
+pub(crate) struct FaultyStream<T> {
+    inner: T,
+    fault: FaultSlot,
+    framing: Framing,
+    /// Corrupted frame being written and the number of its bytes written so far.
+    corrupted: Option<(Vec<u8>, usize)>,
+    /// Resolved once the server stops, then `None`.
+    closed: Option<BoxFuture<'static, ()>>,
+}
+
+/// Service and stream of a new connection to `simulation` ended once all `closed` senders are dropped.
+pub(crate) fn connection<S, T>(
+    simulation: S,
+    stream: T,
+    mut closed: watch::Receiver<()>,
+    framing: Framing,
+) -> (SimulationService<S>, FaultyStream<T>) {
+    let fault = FaultSlot::default();
+    let service = SimulationService {
+        simulation,
+        fault: Some(fault.clone()),
+        discard_broadcast: framing == Framing::Rtu,
+    };
+    let stream = FaultyStream {
+        inner: stream,
+        fault,
+        framing,
+        corrupted: None,
+        closed: Some(
+            async move {
+                // No value is ever sent, only the senders are dropped
+                let _ = closed.changed().await;
+            }
+            .boxed(),
+        ),
+    };
+
+    (service, stream)
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for FaultyStream<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        if let Some(closed) = this.closed.as_mut() {
+            if closed.poll_unpin(cx).is_ready() {
+                this.closed = None;
+            }
+        }
+        if this.closed.is_none() {
+            // End of stream
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for FaultyStream<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        // The server writes a single response frame after each service call
+        if this.corrupted.is_none() {
+            match this.fault.lock().unwrap().take() {
+                Some(FrameFault::Discard) => return Poll::Ready(Ok(buf.len())),
+                Some(FrameFault::Corrupt) => {
+                    let mut frame = buf.to_vec();
+                    corrupt(&mut frame, this.framing);
+                    this.corrupted = Some((frame, 0));
+                }
+                None => {}
+            }
+        }
+        let Some((frame, written)) = this.corrupted.as_mut() else {
+            return Pin::new(&mut this.inner).poll_write(cx, buf);
+        };
+        let n = ready!(Pin::new(&mut this.inner).poll_write(cx, &frame[*written..]))?;
+        *written += n;
+        if *written == frame.len() {
+            this.corrupted = None;
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Corrupt `frame` so that the client rejects it.
+fn corrupt(frame: &mut [u8], framing: Framing) {
+    match framing {
+        // There is no checksum, so the protocol identifier is corrupted instead
+        Framing::Mbap => {
+            if let Some(protocol_id) = frame.get_mut(2..4) {
+                protocol_id.copy_from_slice(&[0xFF, 0xFF]);
+            }
+        }
+        Framing::Rtu => {
+            if let Some(crc) = frame.last_mut() {
+                *crc = !*crc;
+            }
+        }
+    }
+}
+
+/// Errors of single connections, e.g. reset by the peer or sending malformed frames, do not stop the simulator.
+pub(crate) fn ignore_process_error(_err: io::Error) {}
+
+/// Serve Modbus TCP connections accepted by `listener` according to `options` until it fails.
+/// The connections failing the TLS handshake are closed.
+///
+/// The connections are ended when the returned future is dropped.
+pub(crate) async fn serve_tcp<S: Simulation>(
+    listener: TcpListener,
+    simulation: S,
+    options: TcpOptions,
+) -> io::Result<()> {
+    let (_closed_tx, closed_rx) = watch::channel(());
+    let on_connected = |stream, _socket_addr| {
+        let simulation = simulation.clone();
+        let closed_rx = closed_rx.clone();
+        let options = &options;
+        async move {
+            let connection = options
+                .accept(stream)
+                .await
+                .map(|stream| connection(simulation, stream, closed_rx, options.framing));
+            Ok(connection)
+        }
+    };
+    match options.framing {
+        Framing::Mbap => {
+            server::tcp::Server::new(listener)
+                .serve(&on_connected, ignore_process_error)
+                .await
+        }
+        Framing::Rtu => {
+            server::rtu_over_tcp::Server::new(listener)
+                .serve(&on_connected, ignore_process_error)
+                .await
+        }
+    }
+}
+
+/// Serve Modbus UDP requests (MBAP frames in datagrams) until the socket fails.
+///
+/// Malformed datagrams are dropped. UDP gives no delivery guarantee,
+/// so the responses failing to be sent are dropped like the lost ones.
+pub(crate) async fn serve_udp<S: Simulation>(socket: UdpSocket, simulation: S) -> io::Result<()> {
+    let socket = Arc::new(socket);
+    let mut requests = JoinSet::new();
+    let mut buf = [0; MAX_MBAP_FRAME_LEN];
+    loop {
+        tokio::select! {
+            received = socket.recv_from(&mut buf) => {
+                let (len, peer) = received?;
+                let datagram = &buf[..len];
+                let Ok((transaction_id, unit_id, pdu_len)) = frame::decode_mbap_header(datagram) else {
+                    continue;
+                };
+                let Some(pdu) = datagram.get(MBAP_HEADER_LEN..MBAP_HEADER_LEN + pdu_len) else {
+                    continue;
+                };
+                let pdu = pdu.to_vec();
+                let socket = socket.clone();
+                let simulation = simulation.clone();
+                requests.spawn(async move {
+                    let Some((pdu, corrupted)) = reply_pdu(&simulation, unit_id, &pdu).await else {
+                        return;
+                    };
+                    // The client does not match the response with a corrupted transaction id
+                    let transaction_id = if corrupted { !transaction_id } else { transaction_id };
+                    let frame = frame::encode_mbap_frame(transaction_id, unit_id, &pdu);
+                    let _ = socket.send_to(&frame, peer).await;
+                });
+            }
+            // Reap answered requests
+            Some(_) = requests.join_next() => {}
+        }
+    }
+}
+
+/// Call `simulation` with the request PDU and return the response PDU (and whether to corrupt it) if any.
+async fn reply_pdu<S: Simulation>(
+    simulation: &S,
+    slave: u8,
+    pdu: &[u8],
+) -> Option<(Vec<u8>, bool)> {
+    let request = match frame::decode_request(pdu) {
+        Ok(request) => request,
+        Err((fc, exception)) => return Some((frame::encode_response(fc, &Err(exception)), false)),
+    };
+    let fc = request.function_code().value();
+    let req = SlaveRequest { slave, request };
+    match simulation.call(req).await {
+        Reply::Response(result) => Some((frame::encode_response(fc, &result), false)),
+        Reply::CorruptedResponse(result) => Some((frame::encode_response(fc, &result), true)),
+        Reply::NoResponse => None,
+    }
+}
+
+/// Serve Modbus RTU requests received by `serial_stream`.
+///
+/// The response frames can not be discarded or corrupted by the tokio-modbus RTU server,
+/// so unanswered and corrupted replies are answered with [Exception::GatewayTargetDevice] instead.
+pub(crate) async fn serve_rtu<S: Simulation>(
+    serial_stream: SerialStream,
+    simulation: S,
+) -> io::Result<()> {
+    let service = SimulationService {
+        simulation,
+        fault: None,
+        discard_broadcast: false,
+    };
+    server::rtu::Server::new(serial_stream)
+        .serve_forever(service)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use std::time::Duration;
+    use tokio::time::timeout;
+    use tokio_modbus::{
+        client::{rtu, tcp, Reader},
+        slave::{Slave, SlaveContext},
+        Request,
+    };
+
+    use crate::simulator::TcpSimulator;
+
+    #[derive(Debug, Clone)]
+    /// Simulation replying to holding register reads as chosen by the address.
+    struct ReplySimulation;
+
+    impl Simulation for ReplySimulation {
+        fn call(&self, req: SlaveRequest<'static>) -> BoxFuture<'static, Reply> {
+            let response = Ok(Response::ReadHoldingRegisters(vec![42]));
+            let reply = match req.request {
+                Request::ReadHoldingRegisters(0, 1) => Reply::Response(response),
+                Request::ReadHoldingRegisters(1, 1) => Reply::NoResponse,
+                Request::ReadHoldingRegisters(2, 1) => Reply::CorruptedResponse(response),
+                _ => Reply::Response(Err(Exception::IllegalDataAddress)),
+            };
+            future::ready(reply).boxed()
+        }
+
+        fn update_state(&self, _elapsed: Duration) -> BoxFuture<'static, ()> {
+            future::ready(()).boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_tcp() {
+        let handle = TcpSimulator::new("127.0.0.1:0".parse().unwrap(), ReplySimulation)
+            .spawn()
+            .await
+            .unwrap();
+        let socket_addr = handle.local_addr().unwrap();
+        let mut client = tcp::connect_slave(socket_addr, Slave(1)).await.unwrap();
+
+        assert_eq!(
+            client.read_holding_registers(0, 1).await.unwrap(),
+            Ok(vec![42])
+        );
+        assert_eq!(
+            client.read_holding_registers(3, 1).await.unwrap(),
+            Err(Exception::IllegalDataAddress)
+        );
+
+        // The unanswered request times out, but the connection keeps serving
+        let unanswered = timeout(
+            Duration::from_millis(100),
+            client.read_holding_registers(1, 1),
+        );
+        assert!(unanswered.await.is_err());
+        assert_eq!(
+            client.read_holding_registers(0, 1).await.unwrap(),
+            Ok(vec![42])
+        );
+
+        // The corrupted response is rejected
+        let err = client.read_holding_registers(2, 1).await.unwrap_err();
+        assert!(
+            matches!(err, tokio_modbus::Error::Transport(err) if err.kind() == io::ErrorKind::InvalidData)
+        );
+
+        // The connections are ended on shutdown
+        let mut client = tcp::connect_slave(socket_addr, Slave(1)).await.unwrap();
+        handle.shutdown().await.unwrap();
+        assert!(client.read_holding_registers(0, 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_serve_rtu_over_tcp() {
+        let handle = TcpSimulator::new("127.0.0.1:0".parse().unwrap(), ReplySimulation)
+            .with_rtu_framing(true)
+            .spawn()
+            .await
+            .unwrap();
+        let stream = tokio::net::TcpStream::connect(handle.local_addr().unwrap())
+            .await
+            .unwrap();
+        let mut client = rtu::attach_slave(stream, Slave(1));
+
+        assert_eq!(
+            client.read_holding_registers(0, 1).await.unwrap(),
+            Ok(vec![42])
+        );
+
+        // The broadcast request is not answered
+        client.set_slave(Slave(0));
+        let unanswered = timeout(
+            Duration::from_millis(100),
+            client.read_holding_registers(0, 1),
+        );
+        assert!(unanswered.await.is_err());
+        client.set_slave(Slave(1));
+        assert_eq!(
+            client.read_holding_registers(0, 1).await.unwrap(),
+            Ok(vec![42])
+        );
+
+        // The response with corrupted CRC is never accepted
+        let corrupted = timeout(
+            Duration::from_millis(100),
+            client.read_holding_registers(2, 1),
+        );
+        assert!(corrupted.await.is_err());
+    }
+}
@@ -0,0 +1,227 @@
+use std::{io, net::SocketAddr, path::Path, sync::Arc};
+
+use tokio::net::TcpStream;
+use tokio_modbus::{
+    client::{tcp, Context},
+    slave::Slave,
+};
+use tokio_rustls::{
+    client::TlsStream,
+    rustls::{
+        crypto::ring,
+        pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, ServerName},
+        server::WebPkiClientVerifier,
+        ClientConfig, RootCertStore, ServerConfig,
+    },
+    TlsConnector,
+};
+
+pub use tokio_rustls::rustls;
+
+/// Registered port of Modbus/TCP Security.
+pub const MODBUS_SECURITY_PORT: u16 = 802;
+
+/// Read all certificates from PEM file at `path`.
+pub fn load_certs(path: impl AsRef<Path>) -> io::Result<Vec<CertificateDer<'static>>> {
+    CertificateDer::pem_file_iter(path)
+        .map_err(invalid_data)?
+        .map(|cert| cert.map_err(invalid_data))
+        .collect()
+}
+
+/// Read the first private key from PEM file at `path`.
+pub fn load_private_key(path: impl AsRef<Path>) -> io::Result<PrivateKeyDer<'static>> {
+    PrivateKeyDer::from_pem_file(path).map_err(invalid_data)
+}
+
+/// Create TLS server configuration with certificate chain `certs` and its private `key`
+/// verifying the client certificates against non-empty `client_ca_certs`, as Modbus/TCP Security requires mutual authentication.
+///
+/// The role extension of the client certificates is out of scope:
+/// it is neither verified nor exposed, so all authenticated clients are authorized to all requests.
+pub fn server_config(
+    certs: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+    client_ca_certs: Vec<CertificateDer<'static>>,
+) -> io::Result<Arc<ServerConfig>> {
+    if client_ca_certs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "No CA certificates to verify the client certificates",
+        ));
+    }
+    let provider = Arc::new(ring::default_provider());
+    let verifier =
+        WebPkiClientVerifier::builder_with_provider(root_store(client_ca_certs)?, provider.clone())
+            .build()
+            .map_err(invalid_data)?;
+    let config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(invalid_data)?
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(certs, key)
+        .map_err(invalid_data)?;
+
+    Ok(Arc::new(config))
+}
+
+/// Create TLS server configuration with certificate chain `certs` and its private `key` accepting any client.
+///
+/// It does not comply with Modbus/TCP Security requiring mutual authentication, see [server_config].
+pub fn server_config_without_client_auth(
+    certs: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+) -> io::Result<Arc<ServerConfig>> {
+    let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(invalid_data)?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(invalid_data)?;
+
+    Ok(Arc::new(config))
+}
+
+/// Create TLS client configuration verifying the server certificate against `ca_certs`
+/// and authenticating by certificate chain and private key of `client_auth`, if any.
+pub fn client_config(
+    ca_certs: Vec<CertificateDer<'static>>,
+    client_auth: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+) -> io::Result<Arc<ClientConfig>> {
+    let builder = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(invalid_data)?
+        .with_root_certificates(root_store(ca_certs)?);
+    let config = match client_auth {
+        Some((certs, key)) => builder
+            .with_client_auth_cert(certs, key)
+            .map_err(invalid_data)?,
+        None => builder.with_no_client_auth(),
+    };
+
+    Ok(Arc::new(config))
+}
+
+/// Connect to Modbus/TCP Security server at `socket_addr` presenting certificate of `server_name`.
+pub async fn connect(
+    socket_addr: SocketAddr,
+    server_name: ServerName<'static>,
+    config: Arc<ClientConfig>,
+) -> io::Result<TlsStream<TcpStream>> {
+    let stream = TcpStream::connect(socket_addr).await?;
+    TlsConnector::from(config)
+        .connect(server_name, stream)
+        .await
+}
+
+/// Connect to `slave` of Modbus/TCP Security server at `socket_addr` presenting certificate of `server_name`.
+///
+/// The context can be used with [`crate::core::InputRegisterMap`] and [`crate::core::HoldingRegisterMap`],
+/// or by [`crate::client::ManagedClient::new`] to reconnect.
+pub async fn connect_slave(
+    socket_addr: SocketAddr,
+    server_name: ServerName<'static>,
+    config: Arc<ClientConfig>,
+    slave: Slave,
+) -> io::Result<Context> {
+    let stream = connect(socket_addr, server_name, config).await?;
+    Ok(tcp::attach_slave(stream, slave))
+}
+
+fn root_store(certs: Vec<CertificateDer<'static>>) -> io::Result<Arc<RootCertStore>> {
+    let mut roots = RootCertStore::empty();
+    for cert in certs {
+        roots.add(cert).map_err(invalid_data)?;
+    }
+    Ok(Arc::new(roots))
+}
+
+fn invalid_data(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(all(test, feature = "simulator"))]
+mod tests {
+    use super::*;
+    use crate::frame;
+    use crate::simulator::testing::replay_simulator;
+    use crate::simulator::TcpSimulator;
+    use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_modbus::{Request, Response};
+
+    #[tokio::test]
+    async fn test_tls_simulator() {
+        // Self-signed CA issuing the server and client certificates
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+        let issue = |name: &str| {
+            let key = KeyPair::generate().unwrap();
+            let cert = CertificateParams::new(vec![name.to_string()])
+                .unwrap()
+                .signed_by(&key, &ca, &ca_key)
+                .unwrap();
+            (
+                vec![cert.der().clone()],
+                PrivateKeyDer::try_from(key.serialize_der()).unwrap(),
+            )
+        };
+        let (server_certs, server_key) = issue("localhost");
+        let (client_certs, client_key) = issue("client");
+
+        let device = replay_simulator([(
+            1,
+            Request::ReadHoldingRegisters(0, 1),
+            Response::ReadHoldingRegisters(vec![42]),
+        )]);
+        // The client certificates can not be left unverified by mistake
+        assert_eq!(
+            server_config(server_certs.clone(), server_key.clone_key(), Vec::new())
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidInput
+        );
+        let config = server_config(server_certs, server_key, vec![ca.der().clone()]).unwrap();
+        let simulator = TcpSimulator::new("127.0.0.1:0".parse().unwrap(), device)
+            .with_tls(config)
+            .spawn()
+            .await
+            .unwrap();
+        let socket_addr = simulator.local_addr().unwrap();
+        let server_name = ServerName::try_from("localhost").unwrap();
+
+        // Client without certificate is rejected
+        let config = client_config(vec![ca.der().clone()], None).unwrap();
+        let mut stream = connect(socket_addr, server_name.clone(), config)
+            .await
+            .unwrap();
+        let _ = stream
+            .write_all(&frame::encode_mbap_frame(
+                1,
+                1,
+                &[0x03, 0x00, 0x00, 0x00, 0x01],
+            ))
+            .await;
+        assert!(stream.read(&mut [0; 16]).await.is_err());
+
+        let config =
+            client_config(vec![ca.der().clone()], Some((client_certs, client_key))).unwrap();
+        let mut stream = connect(socket_addr, server_name, config).await.unwrap();
+        stream
+            .write_all(&frame::encode_mbap_frame(
+                1,
+                1,
+                &[0x03, 0x00, 0x00, 0x00, 0x01],
+            ))
+            .await
+            .unwrap();
+        let mut response = [0; 11];
+        stream.read_exact(&mut response).await.unwrap();
+        assert_eq!(
+            response.to_vec(),
+            frame::encode_mbap_frame(1, 1, &[0x03, 0x02, 0x00, 0x2A])
+        );
+    }
+}
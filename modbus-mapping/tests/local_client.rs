@@ -0,0 +1,285 @@
+use futures::future;
+use modbus_mapping::core::{FieldStatus, HoldingRegisterMap, InputRegisterMap, UpdateMode};
+use modbus_mapping::derive::{
+    HoldingRegisterMap, HoldingRegisterModel, InputRegisterMap, InputRegisterModel,
+};
+use modbus_mapping::plan::ReadPlan;
+use modbus_mapping::simulator::{
+    DataStore, Device, FaultySimulation, FieldWrite, InputRegisterModel, LocalClient, ModelDevice,
+    Simulation, Simulator,
+};
+use std::time::Duration;
+use tokio_modbus::{client::Context, slave::Slave, Exception, Request, Response};
+
+#[derive(Debug, Clone, Default, InputRegisterMap, InputRegisterModel)]
+#[modbus(max_cnt_per_request = 4)]
+pub struct BatteryInputRegisters {
+    #[modbus(addr = 0, ty = "u32", ord = "be", x = 1.0, unit = "W")]
+    pub power: f32,
+    #[modbus(addr = 2, ty = "u32", ord = "be", x = 100.0, unit = "Wh")]
+    pub state_of_energy: f32,
+    #[modbus(addr = 4, ty = "u32", ord = "be", x = 0.01, unit = "V")]
+    pub voltage: f32,
+}
+
+#[derive(Debug, Clone, Default, InputRegisterMap)]
+pub struct BatteryVoltage {
+    #[modbus(addr = 4, ty = "u32", ord = "be", x = 0.01, unit = "V")]
+    pub voltage: f32,
+}
+
+#[derive(Debug, Clone, Default, HoldingRegisterMap, HoldingRegisterModel)]
+pub struct BatteryHoldingRegisters {
+    #[modbus(addr = 0, ty = "i32", ord = "be", x = 0.01, unit = "W")]
+    pub setpoint: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Battery {
+    ir: BatteryInputRegisters,
+    hr: BatteryHoldingRegisters,
+    data_store: DataStore<BatteryInputRegisters, BatteryHoldingRegisters>,
+    writes: Vec<FieldWrite>,
+}
+
+impl Device for Battery {
+    type InputRegisters = BatteryInputRegisters;
+    type HoldingRegisters = BatteryHoldingRegisters;
+
+    fn service_call(&mut self, req: Request) -> future::Ready<Result<Response, Exception>> {
+        let writes = &mut self.writes;
+        self.data_store
+            .service_call_with_hook(&mut self.hr, req, |write| {
+                if write.name == "setpoint" && write.new.abs() > 10_000.0 {
+                    return Err(Exception::IllegalDataValue);
+                }
+                writes.push(write.clone());
+                Ok(())
+            })
+    }
+
+    fn update_state(&mut self, _elapsed: Duration) {
+        self.ir.power += 100.0;
+        self.ir.voltage = 230.0;
+
+        let _ = self
+            .ir
+            .update_registers(&mut self.data_store.input_registers);
+    }
+}
+
+#[tokio::test]
+async fn test_local_client() {
+    let simulator = Simulator::new(Battery::default());
+    simulator.update_state(Duration::from_secs(1)).await;
+    let local = LocalClient::new(simulator.clone(), Slave(1));
+    let mut client = Context::from(local.clone());
+
+    let ir = BatteryInputRegisters::from_input_registers(&mut client)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(ir.power, 100.0);
+    assert_eq!(ir.voltage, 230.0);
+    // Registers are read in two blocks because of `max_cnt_per_request`
+    assert_eq!(
+        local
+            .take_requests()
+            .into_iter()
+            .map(|req| req.request)
+            .collect::<Vec<_>>(),
+        vec![
+            Request::ReadInputRegisters(0, 4),
+            Request::ReadInputRegisters(4, 2)
+        ]
+    );
+
+    let hr = BatteryHoldingRegisters { setpoint: -50.0 };
+    hr.write_to_registers(&mut client).await.unwrap().unwrap();
+    assert_eq!(simulator.0.lock().await.hr.setpoint, -50.0);
+    assert_eq!(local.requests().len(), 1);
+    assert_eq!(
+        simulator.0.lock().await.writes,
+        vec![FieldWrite {
+            name: "setpoint",
+            old: 0.0,
+            new: -50.0
+        }]
+    );
+
+    // Vetoed by the write hook
+    let hr = BatteryHoldingRegisters { setpoint: 20_000.0 };
+    assert_eq!(
+        hr.write_to_registers(&mut client).await.unwrap(),
+        Err(Exception::IllegalDataValue)
+    );
+    assert_eq!(simulator.0.lock().await.hr.setpoint, -50.0);
+}
+
+#[tokio::test]
+async fn test_model_device() {
+    let device = ModelDevice::new(
+        BatteryInputRegisters::default(),
+        BatteryHoldingRegisters { setpoint: 3600.0 },
+        |ir: &mut BatteryInputRegisters, hr: &mut BatteryHoldingRegisters, elapsed: Duration| {
+            ir.power = hr.setpoint;
+            ir.state_of_energy += ir.power * elapsed.as_secs_f32() / 3600.0;
+        },
+    );
+    let simulator = Simulator::new(device);
+    let mut client = Context::from(LocalClient::new(simulator.clone(), Slave(1)));
+
+    BatteryHoldingRegisters { setpoint: 7200.0 }
+        .write_to_registers(&mut client)
+        .await
+        .unwrap()
+        .unwrap();
+    simulator.update_state(Duration::from_secs(1000)).await;
+
+    let ir = BatteryInputRegisters::from_input_registers(&mut client)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(ir.power, 7200.0);
+    assert_eq!(ir.state_of_energy, 2000.0);
+}
+
+#[tokio::test]
+async fn test_read_plan() {
+    let simulator = Simulator::new(Battery::default());
+    simulator.update_state(Duration::from_secs(1)).await;
+    let local = LocalClient::new(simulator, Slave(1));
+    let mut client = Context::from(local.clone());
+
+    // Both types are read by a single request
+    let mut plan = ReadPlan::new(
+        [
+            BatteryInputRegisters::field_registers(),
+            BatteryVoltage::field_registers(),
+        ]
+        .concat(),
+        [],
+        123,
+        0,
+    );
+    assert_eq!(plan.blocks(), [(0, 6)]);
+    assert_eq!(BatteryVoltage::field_units(), [("voltage", "V")]);
+    let words = plan
+        .read_input_registers(&mut client)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(local.requests().len(), 1);
+
+    let mut ir = BatteryInputRegisters::default();
+    let mut voltage = BatteryVoltage::default();
+    ir.update_from_words(&words).unwrap();
+    voltage.update_from_words(&words).unwrap();
+    assert_eq!(ir.power, 100.0);
+    assert_eq!(voltage.voltage, 230.0);
+
+    // Registers not covered by the plan
+    let mut plan = ReadPlan::new(BatteryVoltage::field_registers(), [], 123, 0);
+    let words = plan
+        .read_input_registers(&mut client)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        ir.update_from_words(&words),
+        Err(Exception::IllegalDataAddress)
+    );
+    ir.voltage = 0.0;
+    assert_eq!(
+        ir.update_from_available_words(&words),
+        vec!["power", "state_of_energy"]
+    );
+    assert_eq!(ir.voltage, 230.0);
+}
+
+#[tokio::test]
+async fn test_read_report() {
+    let simulator = Simulator::new(Battery::default());
+    simulator.update_state(Duration::from_secs(1)).await;
+    // The second block (`voltage`) fails
+    let simulation = FaultySimulation::new(simulator)
+        .with_address_exception(4..=5, Exception::IllegalDataAddress);
+    let mut client = Context::from(LocalClient::new(simulation, Slave(1)));
+
+    let mut ir = BatteryInputRegisters::default();
+    let report = ir
+        .update_from_input_registers_with_report(&mut client, UpdateMode::Atomic)
+        .await
+        .unwrap();
+    assert!(!report.is_ok());
+    assert!(!report.updated);
+    assert_eq!(report.status("power"), Some(FieldStatus::Ok));
+    assert_eq!(
+        report.status("voltage"),
+        Some(FieldStatus::Exception(Exception::IllegalDataAddress))
+    );
+    assert_eq!(ir.power, 0.0);
+
+    let report = ir
+        .update_from_input_registers_with_report(&mut client, UpdateMode::FieldByField)
+        .await
+        .unwrap();
+    assert!(report.updated);
+    assert_eq!(report.fields.len(), 3);
+    assert_eq!(ir.power, 100.0);
+    assert_eq!(ir.voltage, 0.0);
+}
+
+#[derive(Debug, Clone, Default)]
+struct ManualVoltage {
+    voltage: u32,
+}
+
+#[async_trait::async_trait]
+impl InputRegisterMap for ManualVoltage {
+    async fn update_from_input_registers(
+        &mut self,
+        client: &mut dyn tokio_modbus::client::Reader,
+    ) -> tokio_modbus::Result<()> {
+        let words = match client.read_input_registers(4, 2).await? {
+            Ok(words) => words,
+            Err(exc) => return Ok(Err(exc)),
+        };
+        self.voltage = (words[0] as u32) << 16 | words[1] as u32;
+
+        Ok(Ok(()))
+    }
+}
+
+#[tokio::test]
+async fn test_default_read_report() {
+    let simulator = Simulator::new(Battery::default());
+    simulator.update_state(Duration::from_secs(1)).await;
+    let simulation = FaultySimulation::new(simulator.clone())
+        .with_address_exception(4..=5, Exception::IllegalDataAddress);
+    let mut client = Context::from(LocalClient::new(simulation, Slave(1)));
+
+    // The whole type is reported as a single field
+    let mut voltage = ManualVoltage::default();
+    let report = voltage
+        .update_from_input_registers_with_report(&mut client, UpdateMode::FieldByField)
+        .await
+        .unwrap();
+    assert!(!report.is_ok());
+    assert!(!report.updated);
+    assert_eq!(report.fields.len(), 1);
+    assert!(report.fields[0].name.ends_with("ManualVoltage"));
+    assert_eq!(
+        report.fields[0].status,
+        FieldStatus::Exception(Exception::IllegalDataAddress)
+    );
+
+    let mut client = Context::from(LocalClient::new(simulator, Slave(1)));
+    let report = voltage
+        .update_from_input_registers_with_report(&mut client, UpdateMode::Atomic)
+        .await
+        .unwrap();
+    assert!(report.is_ok());
+    assert!(report.updated);
+    assert_eq!(voltage.voltage, 23000);
+}
@@ -0,0 +1,160 @@
+use bytes::BytesMut;
+use modbus_mapping::derive::{
+    HoldingRegisterMap, HoldingRegisterModel, InputRegisterMap, InputRegisterModel,
+};
+use modbus_mapping::mqtt::MqttBridge;
+use modbus_mapping::simulator::{LocalClient, ModelDevice, Simulator};
+use rumqttc::{
+    mqttbytes::{self, v4},
+    AsyncClient, ConnAck, ConnectReturnCode, Event, MqttOptions, Packet, PingResp, PubAck, Publish,
+    QoS, SubAck, SubscribeReasonCode,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    sync::mpsc,
+};
+use tokio_modbus::{client::Context, slave::Slave};
+
+#[derive(Debug, Clone, Default, Serialize, InputRegisterMap, InputRegisterModel)]
+pub struct BatteryInputRegisters {
+    #[modbus(addr = 0, ty = "u32", ord = "be", x = 1.0, unit = "W")]
+    pub power: f32,
+}
+
+#[derive(
+    Debug, Clone, Default, Serialize, Deserialize, HoldingRegisterMap, HoldingRegisterModel,
+)]
+pub struct BatteryHoldingRegisters {
+    #[modbus(addr = 0, ty = "i32", ord = "be", x = 0.01, unit = "W")]
+    pub setpoint: f32,
+}
+
+/// Broker accepting a single client, acknowledging its packets and publishing `setpoint` to each of its subscriptions.
+/// Returns the broker port and the receiver of the client publications.
+async fn mock_broker(setpoint: &'static [u8]) -> (u16, mpsc::UnboundedReceiver<Publish>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (published, receiver) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = BytesMut::new();
+        loop {
+            let packet = match v4::read(&mut buf, 4096) {
+                Ok(packet) => packet,
+                Err(mqttbytes::Error::InsufficientBytes(_)) => {
+                    if stream.read_buf(&mut buf).await.unwrap() == 0 {
+                        return;
+                    }
+                    continue;
+                }
+                Err(err) => panic!("{err:?}"),
+            };
+            let mut out = BytesMut::new();
+            match packet {
+                Packet::Connect(_) => {
+                    ConnAck::new(ConnectReturnCode::Success, false)
+                        .write(&mut out)
+                        .unwrap();
+                }
+                Packet::Subscribe(subscribe) => {
+                    let codes = vec![SubscribeReasonCode::Success(QoS::AtLeastOnce)];
+                    SubAck::new(subscribe.pkid, codes).write(&mut out).unwrap();
+                    for filter in subscribe.filters {
+                        Publish::new(filter.path, QoS::AtMostOnce, setpoint)
+                            .write(&mut out)
+                            .unwrap();
+                    }
+                }
+                Packet::Publish(publish) => {
+                    PubAck::new(publish.pkid).write(&mut out).unwrap();
+                    let _ = published.send(publish);
+                }
+                Packet::PingReq => {
+                    PingResp.write(&mut out).unwrap();
+                }
+                _ => {}
+            }
+            stream.write_all(&out).await.unwrap();
+        }
+    });
+
+    (port, receiver)
+}
+
+#[tokio::test]
+async fn test_mqtt_bridge() {
+    let (port, mut published) =
+        mock_broker(br#"{"setpoint": {"value": -50.0, "unit": "W"}}"#).await;
+    let (mqtt, mut event_loop) = AsyncClient::new(MqttOptions::new("test", "127.0.0.1", port), 10);
+    let bridge = MqttBridge::new(mqtt, "battery");
+
+    let ir = BatteryInputRegisters { power: 100.0 };
+    let simulator = Simulator::new(ModelDevice::new(
+        ir.clone(),
+        BatteryHoldingRegisters::default(),
+        |_: &mut BatteryInputRegisters, _: &mut BatteryHoldingRegisters, _: Duration| {},
+    ));
+    let mut client = Context::from(LocalClient::new(simulator.clone(), Slave(1)));
+
+    bridge.publish_input_registers("input", &ir).await.unwrap();
+    bridge.subscribe_setpoints("holding").await.unwrap();
+
+    // The setpoint is written to the device before it is published back
+    let mut hr = BatteryHoldingRegisters::default();
+    loop {
+        if let Event::Incoming(Packet::Publish(publish)) = event_loop.poll().await.unwrap() {
+            assert!(!bridge
+                .apply_setpoint("input", &mut hr, &publish, &mut client)
+                .await
+                .unwrap()
+                .unwrap());
+            assert!(bridge
+                .apply_setpoint("holding", &mut hr, &publish, &mut client)
+                .await
+                .unwrap()
+                .unwrap());
+            break;
+        }
+    }
+    assert_eq!(hr.setpoint, -50.0);
+    assert_eq!(simulator.0.lock().await.hr.setpoint, -50.0);
+    bridge
+        .publish_holding_registers("holding", &hr)
+        .await
+        .unwrap();
+
+    let mut publications = Vec::new();
+    while publications.len() < 2 {
+        tokio::select! {
+            event = event_loop.poll() => {
+                event.unwrap();
+            }
+            Some(publish) = published.recv() => publications.push(publish),
+        }
+    }
+    let publications: Vec<(String, Value)> = publications
+        .into_iter()
+        .map(|publish| {
+            let json = serde_json::from_slice(&publish.payload).unwrap();
+            (publish.topic, json)
+        })
+        .collect();
+    assert_eq!(
+        publications,
+        [
+            (
+                "battery/input".to_string(),
+                json!({"power": {"value": 100.0, "unit": "W"}})
+            ),
+            (
+                "battery/holding".to_string(),
+                json!({"setpoint": {"value": -50.0, "unit": "W"}})
+            ),
+        ]
+    );
+}